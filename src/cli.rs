@@ -0,0 +1,217 @@
+//! The command line tool. A crate with its own formats can run it with a [`CodecRegistry`] that
+//! includes them:
+//!
+//! ```no_run
+//! use snes_compress::{cli, CodecRegistry};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let registry = CodecRegistry::new();
+//! // registry.register(MyGameCodec);
+//! let args: Vec<String> = std::env::args().collect();
+//! cli::run(&args, &registry)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    error::Error,
+    fs::{self, File},
+    io,
+};
+
+use crate::{
+    Codec, CodecRegistry, CompressionOptions, DecompressionOptions, Decompressor,
+    DisassembledCommand,
+};
+
+const MAX_LISTED_ARGUMENTS: usize = 8;
+
+#[derive(Debug)]
+struct Inputs {
+    option: String,
+    format: String,
+    input_file: String,
+    output_file: String,
+}
+
+/// Runs the command line tool with `args`, including the program name, using the formats in
+/// `registry`. Usage mistakes print the help and return `Ok`, while errors reading, writing or
+/// converting the files are returned.
+pub fn run(args: &[String], registry: &CodecRegistry) -> Result<(), Box<dyn Error>> {
+    if args.len() == 1 {
+        print_all_info(registry);
+        return Ok(());
+    } else if args.len() != 5 && !(args.len() == 4 && args[1] == "-t") {
+        print_usage();
+        return Ok(());
+    }
+    let inputs = get_inputs(args);
+    if !validate_inputs(&inputs, registry) {
+        return Ok(());
+    }
+
+    let in_file = inputs.input_file;
+    let out_file = inputs.output_file;
+    let format_file_codec;
+    let codec = match get_codec(&inputs.format, registry) {
+        Some(codec) => codec,
+        None => {
+            let spec = fs::read_to_string(&inputs.format)?;
+            format_file_codec = crate::parse_format_spec(&spec).map_err(|error| {
+                format!("Could not load the format file {}: {error}", inputs.format)
+            })?;
+            &format_file_codec
+        }
+    };
+    let compression_type = codec.metadata().compression_type;
+
+    if inputs.option == "-t" {
+        let Some(compression_type) = compression_type else {
+            return Err("Listing commands is only supported for built-in formats.".into());
+        };
+        let source = fs::read(&in_file)?;
+        let commands = crate::disassemble(&source, compression_type)?;
+        print_listing(&commands);
+    }
+    if inputs.option == "-d" {
+        let (dlen, clen) = match compression_type {
+            Some(compression_type) => {
+                let source = File::open(&in_file)?;
+                let clen = source.metadata()?.len() as usize;
+                let mut decompressor = Decompressor::new(source, compression_type);
+                let mut out = File::create(&out_file)?;
+                let dlen = io::copy(&mut decompressor, &mut out)? as usize;
+                (dlen, clen)
+            }
+            None => {
+                let source = fs::read(&in_file)?;
+                let options = DecompressionOptions::default();
+                let decompressed = codec.decompress(&source, &options)?;
+                fs::write(&out_file, &decompressed)?;
+                (decompressed.len(), source.len())
+            }
+        };
+        print_finished_stats(&in_file, &out_file, codec.name(), dlen, clen);
+    }
+    if inputs.option == "-c" {
+        let source = fs::read(&in_file)?;
+        let options = CompressionOptions::default();
+        let compressed = codec.compress(&source, &options)?;
+        fs::write(&out_file, &compressed)?;
+        let dlen = source.len();
+        let clen = compressed.len();
+        print_finished_stats(&in_file, &out_file, codec.name(), dlen, clen);
+    }
+    Ok(())
+}
+
+fn print_finished_stats(
+    in_file: &str,
+    out_file: &str,
+    format: &str,
+    decompressed_len: usize,
+    compressed_len: usize,
+) {
+    println!("input_file: {in_file}");
+    println!("output_file: {out_file}");
+    println!("format: {format}");
+    println!("decompressed_size: {decompressed_len:X}");
+    println!("compressed_size: {compressed_len:X}");
+    println!();
+    println!("Done!");
+}
+
+fn print_listing(commands: &[DisassembledCommand]) {
+    println!("offset  cmd  ext  size  output         arguments");
+    for command in commands {
+        let offset = command.offset;
+        let cmd_num = command.cmd_num;
+        let ext = if command.is_extended { "ext" } else { "   " };
+        let cmd_size = command.cmd_size;
+        let output_start = command.output_range.start;
+        let output_end = command.output_range.end;
+        let mut arguments: Vec<String> = command
+            .arguments
+            .iter()
+            .take(MAX_LISTED_ARGUMENTS)
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+        if command.arguments.len() > MAX_LISTED_ARGUMENTS {
+            arguments.push("..".to_owned());
+        }
+        let arguments = arguments.join(" ");
+        println!(
+            "{offset:06X}  {cmd_num:03b}  {ext}  {cmd_size:4X}  {output_start:06X}-{output_end:06X}  {arguments}"
+        );
+    }
+}
+
+fn validate_inputs(inputs: &Inputs, registry: &CodecRegistry) -> bool {
+    if inputs.option != "-d" && inputs.option != "-c" && inputs.option != "-t" {
+        print_options();
+        return false;
+    }
+    if inputs.format.starts_with('-') && get_codec(&inputs.format, registry).is_none() {
+        print_formats(registry);
+        return false;
+    }
+    if !inputs.format.starts_with('-') && fs::metadata(&inputs.format).is_err() {
+        println!("The format file {} could not be found.", inputs.format);
+        return false;
+    }
+    true
+}
+
+fn get_codec<'a>(format: &str, registry: &'a CodecRegistry) -> Option<&'a dyn Codec> {
+    registry.get(format.strip_prefix('-')?)
+}
+
+fn get_inputs(args: &[String]) -> Inputs {
+    let option = args[1].clone();
+    let format = args[2].clone();
+    let input_file = args[3].clone();
+    let output_file = args.get(4).cloned().unwrap_or_default();
+    Inputs {
+        option,
+        format,
+        input_file,
+        output_file,
+    }
+}
+
+fn print_all_info(registry: &CodecRegistry) {
+    print_info();
+    print_usage();
+    print_options();
+    print_formats(registry);
+}
+
+fn print_info() {
+    println!("snes_compress:");
+    println!("    For compressing and decompressing data for old games.");
+    println!();
+}
+
+fn print_usage() {
+    println!("Usage:");
+    println!("    snes_compress [option] [format] <input_file> <output_file>");
+    println!("    snes_compress -t [format] <input_file>");
+    println!();
+}
+
+fn print_options() {
+    println!("Options:");
+    println!("    -d: decompress");
+    println!("    -c: compress");
+    println!("    -t: list the commands in a compressed file");
+    println!();
+}
+
+fn print_formats(registry: &CodecRegistry) {
+    println!("Formats:");
+    for codec in registry.codecs() {
+        println!("    -{}: {}", codec.name(), codec.metadata().description);
+    }
+    println!("    <format_file>: a custom LZ format described in a text file");
+    println!();
+}
@@ -0,0 +1,420 @@
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::errors::{CompressionError, DecompressionError};
+use crate::{
+    compress_with_options, compress_with_strategy, decompress_with_options,
+    decompress_with_strategy, verify_with_framing, CompressionOptions, CompressionStrategy,
+    CompressionType, DecompressionOptions, DecompressionStrategy,
+};
+
+const BUILT_IN_TYPES: [CompressionType; 8] = [
+    CompressionType::LZ1,
+    CompressionType::LZ2,
+    CompressionType::LZ3,
+    CompressionType::LZ4,
+    CompressionType::LZ5,
+    CompressionType::LZ19,
+    CompressionType::RLE1,
+    CompressionType::RLE2,
+];
+
+/// Details about a codec, used when listing formats.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodecMetadata {
+    /// A short description of the format.
+    pub description: String,
+    /// The built-in format the codec implements, if any. Only built-in formats can be streamed
+    /// with [`Decompressor`](crate::Decompressor) or listed with [`disassemble`](crate::disassemble).
+    pub compression_type: Option<CompressionType>,
+}
+
+/// A compression format.
+///
+/// Every [`CompressionType`] is a codec. Other formats can implement this trait and be added to a
+/// [`CodecRegistry`], which makes them available to the command line tool through
+/// [`cli::run`](crate::cli::run).
+pub trait Codec {
+    /// The name used to look up the codec, like `"LZ5"`. Lookups ignore ASCII case.
+    fn name(&self) -> &str;
+
+    fn metadata(&self) -> CodecMetadata;
+
+    /// Compresses `source`. If `options.verify` is set, the output should be checked with
+    /// [`verify`](crate::verify).
+    fn compress(
+        &self,
+        source: &[u8],
+        options: &CompressionOptions,
+    ) -> Result<Vec<u8>, CompressionError>;
+
+    fn decompress(
+        &self,
+        source: &[u8],
+        options: &DecompressionOptions,
+    ) -> Result<Vec<u8>, DecompressionError>;
+}
+
+impl Codec for CompressionType {
+    fn name(&self) -> &str {
+        match self {
+            CompressionType::LZ1 => "LZ1",
+            CompressionType::LZ2 => "LZ2",
+            CompressionType::LZ3 => "LZ3",
+            CompressionType::LZ4 => "LZ4",
+            CompressionType::LZ5 => "LZ5",
+            CompressionType::LZ19 => "LZ19",
+            CompressionType::RLE1 => "RLE1",
+            CompressionType::RLE2 => "RLE2",
+        }
+    }
+
+    fn metadata(&self) -> CodecMetadata {
+        let description = match self {
+            CompressionType::LZ1 => "Lunar Compress LC_LZ1",
+            CompressionType::LZ2 => "Lunar Compress LC_LZ2",
+            CompressionType::LZ3 => "LZ3, as in the Pokemon Crystal disassembly",
+            CompressionType::LZ4 => "Lunar Compress LC_LZ4",
+            CompressionType::LZ5 => "Lunar Compress LC_LZ5",
+            CompressionType::LZ19 => "LC_LZ5 with zero fill instead of increasing fill",
+            CompressionType::RLE1 => "Run length encoding",
+            CompressionType::RLE2 => "Run length encoding of each 2bpp bitplane",
+        };
+        CodecMetadata {
+            description: description.to_string(),
+            compression_type: Some(*self),
+        }
+    }
+
+    fn compress(
+        &self,
+        source: &[u8],
+        options: &CompressionOptions,
+    ) -> Result<Vec<u8>, CompressionError> {
+        compress_with_options(source, *self, options)
+    }
+
+    fn decompress(
+        &self,
+        source: &[u8],
+        options: &DecompressionOptions,
+    ) -> Result<Vec<u8>, DecompressionError> {
+        decompress_with_options(source, *self, options)
+    }
+}
+
+/// A format made of LZ commands, described by a pair of strategies.
+///
+/// ```
+/// use snes_compress::{compression_commands as comp, decompression_commands as decomp};
+/// use snes_compress::{Codec, CompressionStrategy, DecompressionStrategy, LzCodec};
+///
+/// // LZ2 with the fill commands numbered the other way around.
+/// let compression_strategy = CompressionStrategy::new(3, 1024)
+///     .set_fallback_command(0b000, false, comp::direct_copy)
+///     .insert_command(0b010, false, comp::byte_fill)
+///     .insert_command(0b001, false, comp::word_fill)
+///     .insert_command(0b100, false, comp::repeat_be);
+/// let decompression_strategy = DecompressionStrategy::new()
+///     .insert_command(0b000, false, decomp::direct_copy)
+///     .insert_command(0b010, false, decomp::byte_fill)
+///     .insert_command(0b001, false, decomp::word_fill)
+///     .insert_command(0b100, false, decomp::repeat_be);
+/// let codec = LzCodec::new("MyGame", compression_strategy, decompression_strategy);
+///
+/// let source = b"ABABABAB CCCCCCCC ABABABAB";
+/// let compressed = codec.compress(source, &Default::default()).unwrap();
+/// assert_eq!(codec.decompress(&compressed, &Default::default()).unwrap(), source);
+/// ```
+#[derive(Clone)]
+pub struct LzCodec {
+    name: String,
+    description: String,
+    compression_strategy: CompressionStrategy,
+    decompression_strategy: DecompressionStrategy,
+}
+
+impl LzCodec {
+    pub fn new(
+        name: &str,
+        compression_strategy: CompressionStrategy,
+        decompression_strategy: DecompressionStrategy,
+    ) -> Self {
+        LzCodec {
+            name: name.to_string(),
+            description: String::new(),
+            compression_strategy,
+            decompression_strategy,
+        }
+    }
+
+    pub fn set_description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+}
+
+impl Codec for LzCodec {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> CodecMetadata {
+        CodecMetadata {
+            description: self.description.clone(),
+            compression_type: None,
+        }
+    }
+
+    fn compress(
+        &self,
+        source: &[u8],
+        options: &CompressionOptions,
+    ) -> Result<Vec<u8>, CompressionError> {
+        let compressed = compress_with_strategy(source, &self.compression_strategy, options)?;
+        if options.verify {
+            verify_with_framing(source, &compressed, self, options.framing)?;
+        }
+        Ok(compressed)
+    }
+
+    fn decompress(
+        &self,
+        source: &[u8],
+        options: &DecompressionOptions,
+    ) -> Result<Vec<u8>, DecompressionError> {
+        decompress_with_strategy(source, &self.decompression_strategy, options)
+    }
+}
+
+/// A set of codecs, looked up by name or by [`CompressionType`].
+pub struct CodecRegistry {
+    codecs: Vec<Box<dyn Codec>>,
+}
+
+impl CodecRegistry {
+    /// Creates a registry holding every built-in format.
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        for compression_type in BUILT_IN_TYPES {
+            registry.register(compression_type);
+        }
+        registry
+    }
+
+    /// Creates a registry with no codecs.
+    pub fn empty() -> Self {
+        CodecRegistry { codecs: Vec::new() }
+    }
+
+    /// Adds `codec`, replacing any codec with the same name.
+    pub fn register<C: Codec + 'static>(&mut self, codec: C) {
+        let existing = self
+            .codecs
+            .iter()
+            .position(|registered| registered.name().eq_ignore_ascii_case(codec.name()));
+        match existing {
+            Some(index) => self.codecs[index] = Box::new(codec),
+            None => self.codecs.push(Box::new(codec)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Codec> {
+        self.codecs
+            .iter()
+            .find(|codec| codec.name().eq_ignore_ascii_case(name))
+            .map(|codec| codec.as_ref())
+    }
+
+    /// Returns the codec registered under the name of `compression_type`.
+    pub fn get_by_type(&self, compression_type: CompressionType) -> Option<&dyn Codec> {
+        self.get(compression_type.name())
+    }
+
+    /// Returns every codec, in the order they were registered.
+    pub fn codecs(&self) -> impl Iterator<Item = &dyn Codec> {
+        self.codecs.iter().map(|codec| codec.as_ref())
+    }
+}
+
+impl Default for CodecRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::DecompressionErrorKind;
+
+    /// Stores the data as is, after a one byte marker.
+    struct StoredCodec;
+
+    impl Codec for StoredCodec {
+        fn name(&self) -> &str {
+            "Stored"
+        }
+
+        fn metadata(&self) -> CodecMetadata {
+            CodecMetadata {
+                description: "Uncompressed data".to_string(),
+                compression_type: None,
+            }
+        }
+
+        fn compress(
+            &self,
+            source: &[u8],
+            options: &CompressionOptions,
+        ) -> Result<Vec<u8>, CompressionError> {
+            let mut compressed = vec![0x00];
+            compressed.extend_from_slice(source);
+            if options.verify {
+                crate::verify(source, &compressed, self)?;
+            }
+            Ok(compressed)
+        }
+
+        fn decompress(
+            &self,
+            source: &[u8],
+            _options: &DecompressionOptions,
+        ) -> Result<Vec<u8>, DecompressionError> {
+            match source.split_first() {
+                Some((0x00, data)) => Ok(data.to_vec()),
+                _ => {
+                    let kind = DecompressionErrorKind::InvalidCommand;
+                    Err(DecompressionError::new(kind, 0, source.first().copied(), 0))
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn registry_finds_built_in_formats() {
+        let registry = CodecRegistry::new();
+        for compression_type in BUILT_IN_TYPES {
+            let codec = registry.get_by_type(compression_type).unwrap();
+            assert_eq!(codec.metadata().compression_type, Some(compression_type));
+            assert_eq!(codec.name(), compression_type.to_string());
+        }
+        assert_eq!(registry.codecs().count(), BUILT_IN_TYPES.len());
+    }
+
+    #[test]
+    fn registry_lookup_ignores_case() {
+        let registry = CodecRegistry::new();
+        assert_eq!(registry.get("lz19").unwrap().name(), "LZ19");
+        assert!(registry.get("LZ6").is_none());
+    }
+
+    #[test]
+    fn built_in_codec_matches_functions() {
+        let source = b"ABABABABCCCCCCCCDEFDEFDEF".to_vec();
+        let registry = CodecRegistry::new();
+        let codec = registry.get("LZ5").unwrap();
+        let compressed = codec
+            .compress(&source, &CompressionOptions::default())
+            .unwrap();
+        assert_eq!(
+            compressed,
+            crate::compress(&source, CompressionType::LZ5).unwrap()
+        );
+        let decompressed = codec
+            .decompress(&compressed, &DecompressionOptions::default())
+            .unwrap();
+        assert_eq!(decompressed, source);
+    }
+
+    #[test]
+    fn registry_accepts_custom_codecs() {
+        let mut registry = CodecRegistry::empty();
+        registry.register(StoredCodec);
+        let codec = registry.get("stored").unwrap();
+        let options = CompressionOptions::new().set_verify(true);
+        let compressed = codec.compress(&[0xA1, 0xA2], &options).unwrap();
+        assert_eq!(compressed, vec![0x00, 0xA1, 0xA2]);
+        assert!(registry.get_by_type(CompressionType::LZ5).is_none());
+    }
+
+    #[test]
+    fn register_replaces_codec_with_same_name() {
+        struct ReplacementCodec;
+
+        impl Codec for ReplacementCodec {
+            fn name(&self) -> &str {
+                "lz5"
+            }
+
+            fn metadata(&self) -> CodecMetadata {
+                CodecMetadata {
+                    description: "Replacement".to_string(),
+                    compression_type: None,
+                }
+            }
+
+            fn compress(
+                &self,
+                source: &[u8],
+                options: &CompressionOptions,
+            ) -> Result<Vec<u8>, CompressionError> {
+                StoredCodec.compress(source, options)
+            }
+
+            fn decompress(
+                &self,
+                source: &[u8],
+                options: &DecompressionOptions,
+            ) -> Result<Vec<u8>, DecompressionError> {
+                StoredCodec.decompress(source, options)
+            }
+        }
+
+        let mut registry = CodecRegistry::new();
+        registry.register(ReplacementCodec);
+        assert_eq!(registry.codecs().count(), BUILT_IN_TYPES.len());
+        let codec = registry.get_by_type(CompressionType::LZ5).unwrap();
+        assert_eq!(codec.metadata().description, "Replacement");
+    }
+
+    #[test]
+    fn lz_codec_matches_built_in_format() {
+        use crate::{compression_commands as comp, decompression_commands as decomp};
+
+        let compression_strategy = CompressionStrategy::new(3, 1024)
+            .set_fallback_command(0b000, false, comp::direct_copy)
+            .insert_command(0b001, false, comp::byte_fill)
+            .insert_command(0b010, false, comp::word_fill)
+            .insert_command(0b011, false, comp::increasing_fill)
+            .insert_command(0b100, false, comp::repeat_be);
+        let decompression_strategy = DecompressionStrategy::new()
+            .insert_command(0b000, false, decomp::direct_copy)
+            .insert_command(0b001, false, decomp::byte_fill)
+            .insert_command(0b010, false, decomp::word_fill)
+            .insert_command(0b011, false, decomp::increasing_fill)
+            .insert_command(0b100, false, decomp::repeat_be);
+        let mut registry = CodecRegistry::empty();
+        registry.register(LzCodec::new(
+            "Custom",
+            compression_strategy,
+            decompression_strategy,
+        ));
+        let codec = registry.get("custom").unwrap();
+
+        let source: Vec<u8> = (0..0x800_u32).map(|i| (i * i / 7) as u8).collect();
+        let options = CompressionOptions::new().set_verify(true);
+        let compressed = codec.compress(&source, &options).unwrap();
+        assert_eq!(
+            compressed,
+            crate::compress(&source, CompressionType::LZ2).unwrap()
+        );
+        let decompressed = codec
+            .decompress(&compressed, &DecompressionOptions::default())
+            .unwrap();
+        assert_eq!(decompressed, source);
+    }
+}
@@ -1,3 +1,5 @@
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+
 pub struct Block {
     pub index: usize,
     pub num_bytes_consumed: usize,
@@ -24,6 +26,10 @@ impl Block {
         self.data.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
     pub fn is_better(&self, other: &Block) -> bool {
         if self.difference() == other.difference() {
             self.ratio() < other.ratio()
@@ -40,7 +46,9 @@ impl Block {
         self.len() as f32 / self.num_bytes_consumed as f32
     }
 
-    pub fn difference(&self) -> usize {
-        self.num_bytes_consumed - self.len()
+    /// The bytes saved by the block, which is negative for a block that writes more bytes than
+    /// it consumes, like a direct copy.
+    pub fn difference(&self) -> isize {
+        self.num_bytes_consumed as isize - self.len() as isize
     }
 }
@@ -1,14 +1,27 @@
-use super::{block::Block, command_callbacks::CommandCallback, history_table::HistoryTable};
+use alloc::vec::Vec;
+use core::cmp;
 
+use super::{
+    block::Block,
+    command_callbacks::{build_command_bytes, CommandCallback},
+    history_table::HistoryTable,
+};
+
+/// How a command is numbered and how its header is written, passed to its callback.
+#[derive(Clone)]
 pub struct CommandConfiguration {
     pub cmd_num: u8,
     pub cmd_size: usize,
+    /// The most bytes the command writes.
     pub max_block_size: usize,
     pub is_extended_only: bool,
 }
 
 impl CommandConfiguration {
+    /// Creates a configuration, clamping `cmd_size` and `max_block_size` like
+    /// [`clamp_sizes`](CommandConfiguration::clamp_sizes) so that every header can be written.
     pub fn new(cmd_num: u8, cmd_size: usize, max_block_size: usize, is_extended_only: bool) -> Self {
+        let (cmd_size, max_block_size) = Self::clamp_sizes(cmd_size, max_block_size);
         CommandConfiguration {
             cmd_num,
             cmd_size,
@@ -16,8 +29,44 @@ impl CommandConfiguration {
             is_extended_only,
         }
     }
+
+    /// Clamps `cmd_size` to 1 to
+    /// [`MAX_NUM_CMD_BITS`](crate::DecompressionStrategy::MAX_NUM_CMD_BITS), and `max_block_size`
+    /// to 1 to the `2^(16 - 2 * cmd_size)` bytes a long form header can hold.
+    pub fn clamp_sizes(cmd_size: usize, max_block_size: usize) -> (usize, usize) {
+        let cmd_size = cmd_size.clamp(1, crate::DecompressionStrategy::MAX_NUM_CMD_BITS);
+        let max_block_size = max_block_size.clamp(1, 1 << (16 - 2 * cmd_size));
+        (cmd_size, max_block_size)
+    }
+
+    pub fn is_extended(&self, num_bytes_consumed: usize) -> bool {
+        let extended_threshold = 2_usize.pow(8 - self.cmd_size as u32);
+        let size = num_bytes_consumed - 1;
+        size >= extended_threshold || self.is_extended_only
+    }
+
+    pub fn header_size(&self, num_bytes_consumed: usize) -> usize {
+        if self.is_extended(num_bytes_consumed) {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Lowers `max_block_size` so that no header of the command starts with `terminator`, which
+    /// would end the stream early. The long form sizes come last, so a command whose long form
+    /// can reach the terminator, like LZ5's `0b111`, is only cut short.
+    pub fn avoid_terminator(mut self, terminator: u8) -> Self {
+        let first_clash = (1..=self.max_block_size)
+            .find(|&size| build_command_bytes(&self, size).first() == Some(&terminator));
+        if let Some(size) = first_clash {
+            self.max_block_size = size - 1;
+        }
+        self
+    }
 }
 
+#[derive(Clone)]
 pub struct Command {
     config: CommandConfiguration,
     callback: CommandCallback,
@@ -29,6 +78,28 @@ impl Command {
     }
 
     pub fn call(&self, source: &[u8], index: usize, history_table: &HistoryTable) -> Option<Block> {
+        let source = &source[..cmp::min(source.len(), self.config.max_block_size)];
         (self.callback)(&self.config, source, index, history_table)
     }
+
+    pub fn config(&self) -> &CommandConfiguration {
+        &self.config
+    }
+
+    pub fn callback(&self) -> CommandCallback {
+        self.callback
+    }
+
+    pub fn max_block_size(&self) -> usize {
+        self.config.max_block_size
+    }
+
+    pub fn header_size(&self, num_bytes_consumed: usize) -> usize {
+        self.config.header_size(num_bytes_consumed)
+    }
+
+    /// Builds the header of the command for a block writing `num_bytes_consumed` bytes.
+    pub fn header(&self, num_bytes_consumed: usize) -> Vec<u8> {
+        build_command_bytes(&self.config, num_bytes_consumed)
+    }
 }
@@ -1,9 +1,21 @@
-use std::cmp;
+//! The commands that LZ formats are made of, for building a
+//! [`CompressionStrategy`](crate::CompressionStrategy).
+//!
+//! Each callback looks at `source`, which starts at `index` of the whole input and holds at most
+//! one block's worth of bytes. It returns the encoded block, or `None` if the command cannot
+//! write the data there.
 
-use super::{block::Block, command::CommandConfiguration, history_table::HistoryTable};
+use alloc::{borrow::ToOwned, vec, vec::Vec};
+use core::cmp;
+
+pub use super::{block::Block, history_table::HistoryTable};
+use super::{command::CommandConfiguration, history_table::RepeatInformation};
 
 pub type CommandCallback = fn(&CommandConfiguration, &[u8], usize, &HistoryTable) -> Option<Block>;
 
+/// The furthest back a two byte absolute offset can reach. Repeats starting past it are skipped.
+const MAX_ABSOLUTE_OFFSET: usize = 0xFFFF;
+
 pub fn direct_copy(
     cmd_config: &CommandConfiguration,
     source: &[u8],
@@ -106,13 +118,34 @@ pub fn increasing_fill(
     Some(block)
 }
 
+pub fn zero_fill(
+    cmd_config: &CommandConfiguration,
+    source: &[u8],
+    index: usize,
+    _history: &HistoryTable,
+) -> Option<Block> {
+    let num_bytes_consumed = source.iter().take_while(|&&byte| byte == 0).count();
+    if num_bytes_consumed == 0 {
+        return None;
+    }
+
+    let data = build_command_bytes(cmd_config, num_bytes_consumed);
+    if num_bytes_consumed <= data.len() {
+        return None;
+    }
+    let block = Block::new(index, num_bytes_consumed, data).set_debug_message("zero fill");
+    Some(block)
+}
+
 pub fn repeat_le(
     cmd_config: &CommandConfiguration,
     source: &[u8],
     index: usize,
     history: &HistoryTable,
 ) -> Option<Block> {
-    let repeat_info = history.find_longest_repeat(source, 0)?;
+    let repeat_info = history
+        .find_longest_repeat(source, 0)
+        .filter(|info| info.start_index <= MAX_ABSOLUTE_OFFSET)?;
 
     let num_bytes_consumed = repeat_info.size;
     if num_bytes_consumed == 0 {
@@ -128,13 +161,39 @@ pub fn repeat_le(
     Some(block)
 }
 
+pub fn repeat_be(
+    cmd_config: &CommandConfiguration,
+    source: &[u8],
+    index: usize,
+    history: &HistoryTable,
+) -> Option<Block> {
+    let repeat_info = history
+        .find_longest_repeat(source, 0)
+        .filter(|info| info.start_index <= MAX_ABSOLUTE_OFFSET)?;
+
+    let num_bytes_consumed = repeat_info.size;
+    if num_bytes_consumed == 0 {
+        return None;
+    }
+
+    let mut data = build_command_bytes(cmd_config, num_bytes_consumed);
+    data.append(&mut transform_into_bytes_be(repeat_info.start_index));
+    if num_bytes_consumed <= data.len() {
+        return None;
+    }
+    let block = Block::new(index, num_bytes_consumed, data).set_debug_message("repeat be");
+    Some(block)
+}
+
 pub fn xor_repeat_le(
     cmd_config: &CommandConfiguration,
     source: &[u8],
     index: usize,
     history: &HistoryTable,
 ) -> Option<Block> {
-    let repeat_info = history.find_longest_repeat_xor(source, 0)?;
+    let repeat_info = history
+        .find_longest_repeat_xor(source, 0)
+        .filter(|info| info.start_index <= MAX_ABSOLUTE_OFFSET)?;
 
     let num_bytes_consumed = repeat_info.size;
     if num_bytes_consumed == 0 {
@@ -150,6 +209,30 @@ pub fn xor_repeat_le(
     Some(block)
 }
 
+pub fn xor_repeat_be(
+    cmd_config: &CommandConfiguration,
+    source: &[u8],
+    index: usize,
+    history: &HistoryTable,
+) -> Option<Block> {
+    let repeat_info = history
+        .find_longest_repeat_xor(source, 0)
+        .filter(|info| info.start_index <= MAX_ABSOLUTE_OFFSET)?;
+
+    let num_bytes_consumed = repeat_info.size;
+    if num_bytes_consumed == 0 {
+        return None;
+    }
+
+    let mut data = build_command_bytes(cmd_config, num_bytes_consumed);
+    data.append(&mut transform_into_bytes_be(repeat_info.start_index));
+    if num_bytes_consumed <= data.len() {
+        return None;
+    }
+    let block = Block::new(index, num_bytes_consumed, data).set_debug_message("xor repeat be");
+    Some(block)
+}
+
 pub fn negative_repeat(
     cmd_config: &CommandConfiguration,
     source: &[u8],
@@ -197,18 +280,96 @@ pub fn negative_xor_repeat(
     Some(block)
 }
 
+pub fn signed_repeat(
+    cmd_config: &CommandConfiguration,
+    source: &[u8],
+    index: usize,
+    history: &HistoryTable,
+) -> Option<Block> {
+    let lower_bound = index - cmp::min(128, index);
+    let relative_info = history.find_longest_repeat(source, lower_bound);
+    let absolute_info = history.find_longest_repeat(source, 0);
+    let block = build_signed_offset_block(cmd_config, index, relative_info, absolute_info)?;
+    Some(block.set_debug_message("signed repeat"))
+}
+
+pub fn signed_bit_reversed_repeat(
+    cmd_config: &CommandConfiguration,
+    source: &[u8],
+    index: usize,
+    history: &HistoryTable,
+) -> Option<Block> {
+    let lower_bound = index - cmp::min(128, index);
+    let relative_info = history.find_longest_repeat_bit_reversed(source, lower_bound);
+    let absolute_info = history.find_longest_repeat_bit_reversed(source, 0);
+    let block = build_signed_offset_block(cmd_config, index, relative_info, absolute_info)?;
+    Some(block.set_debug_message("signed bit reversed repeat"))
+}
+
+pub fn signed_backwards_repeat(
+    cmd_config: &CommandConfiguration,
+    source: &[u8],
+    index: usize,
+    history: &HistoryTable,
+) -> Option<Block> {
+    let lower_bound = index - cmp::min(128, index);
+    let relative_info = history.find_longest_repeat_reversed(source, index, lower_bound);
+    let absolute_info = history.find_longest_repeat_reversed(source, index, 0);
+    let block = build_signed_offset_block(cmd_config, index, relative_info, absolute_info)?;
+    Some(block.set_debug_message("signed backwards repeat"))
+}
+
+/// Builds a block for the LZ3 style offsets, where a set high bit marks a one byte offset
+/// relative to `index` and a clear high bit marks a two byte big endian absolute offset.
+fn build_signed_offset_block(
+    cmd_config: &CommandConfiguration,
+    index: usize,
+    relative_info: Option<RepeatInformation>,
+    absolute_info: Option<RepeatInformation>,
+) -> Option<Block> {
+    let absolute = absolute_info
+        .filter(|info| info.start_index < 0x8000)
+        .map(|info| (info.size, transform_into_bytes_be(info.start_index)));
+    let relative = relative_info.map(|info| {
+        let distance = (index - info.start_index - 1) as u8;
+        (info.size, vec![0x80 | distance])
+    });
+
+    let (num_bytes_consumed, mut arguments) = [absolute, relative]
+        .into_iter()
+        .flatten()
+        .max_by_key(|(size, arguments)| size.saturating_sub(arguments.len()))?;
+
+    let mut data = build_command_bytes(cmd_config, num_bytes_consumed);
+    data.append(&mut arguments);
+    if num_bytes_consumed <= data.len() {
+        return None;
+    }
+    Some(Block::new(index, num_bytes_consumed, data))
+}
+
 fn transform_into_bytes_le(val: usize) -> Vec<u8> {
     let first = val as u8;
     let second = (val >> 8) as u8;
     vec![first, second]
 }
 
-fn build_command_bytes(cmd_config: &CommandConfiguration, num_bytes_consumed: usize) -> Vec<u8> {
+fn transform_into_bytes_be(val: usize) -> Vec<u8> {
+    let first = (val >> 8) as u8;
+    let second = val as u8;
+    vec![first, second]
+}
+
+/// Builds the header of a command writing `num_bytes_consumed` bytes, in the short form if the
+/// size fits and the command allows it.
+pub fn build_command_bytes(
+    cmd_config: &CommandConfiguration,
+    num_bytes_consumed: usize,
+) -> Vec<u8> {
     let cmd = cmd_config.cmd_num;
     let cmd_size = cmd_config.cmd_size;
-    let extended_threshold = 2_usize.pow(8 - cmd_size as u32);
     let size = num_bytes_consumed - 1;
-    let is_extended = size >= extended_threshold || cmd_config.is_extended_only;
+    let is_extended = cmd_config.is_extended(num_bytes_consumed);
 
     let shift_width = 8 - cmd_size;
     let extended_mask = {
@@ -358,6 +519,27 @@ mod tests {
         assert!(block.is_none());
     }
 
+    #[test]
+    fn repeat_be_works() {
+        let cmd_config = CommandConfiguration::new(0b100, 3, 1024, false);
+        let source = b"ASDF_APPLEAPPLE";
+        let mut history_table = HistoryTable::new(source);
+        history_table.insert(b'A', b'P', 5);
+        let block = repeat_be(&cmd_config, &source[10..], 10, &history_table).unwrap();
+        assert_eq!(block.index, 10);
+        assert_eq!(block.num_bytes_consumed, 5);
+        assert_eq!(block.data, vec![0x84, 0x00, 0x05]);
+    }
+
+    #[test]
+    fn repeat_be_returns_none_if_source_is_empty() {
+        let cmd_config = CommandConfiguration::new(0b100, 3, 1024, false);
+        let source = &[];
+        let history_table = HistoryTable::new(source);
+        let block = repeat_be(&cmd_config, source, 0, &history_table);
+        assert!(block.is_none());
+    }
+
     #[test]
     fn xor_repeat_le_works() {
         let cmd_config = CommandConfiguration::new(0b101, 3, 1024, false);
@@ -380,6 +562,28 @@ mod tests {
         assert!(block.is_none());
     }
 
+    #[test]
+    fn xor_repeat_be_works() {
+        let cmd_config = CommandConfiguration::new(0b101, 3, 1024, false);
+        let mut source: Vec<u8> = b"ASDF_APPLE".to_vec();
+        source.append(&mut b"APPLE".iter().map(|&x| x ^ 0xFF).collect());
+        let mut history_table = HistoryTable::new(&source);
+        history_table.insert(b'A', b'P', 5);
+        let block = xor_repeat_be(&cmd_config, &source[10..], 10, &history_table).unwrap();
+        assert_eq!(block.index, 10);
+        assert_eq!(block.num_bytes_consumed, 5);
+        assert_eq!(block.data, vec![0xA4, 0x00, 0x05]);
+    }
+
+    #[test]
+    fn xor_repeat_be_returns_none_if_source_is_empty() {
+        let cmd_config = CommandConfiguration::new(0b101, 3, 1024, false);
+        let source = &[];
+        let history_table = HistoryTable::new(source);
+        let block = xor_repeat_be(&cmd_config, source, 0, &history_table);
+        assert!(block.is_none());
+    }
+
     #[test]
     fn negative_repeat_works() {
         let cmd_config = CommandConfiguration::new(0b110, 3, 1024, false);
@@ -422,4 +626,90 @@ mod tests {
         let block = xor_repeat_le(&cmd_config, source, 0, &history_table);
         assert!(block.is_none());
     }
+
+    #[test]
+    fn zero_fill_works() {
+        let cmd_config = CommandConfiguration::new(0b011, 3, 1024, false);
+        let source = &[0x00, 0x00, 0x00, 0x00, 0x00, 0xAA];
+        let history_table = HistoryTable::new(source);
+        let block = zero_fill(&cmd_config, source, 0, &history_table).unwrap();
+        assert_eq!(block.index, 0);
+        assert_eq!(block.num_bytes_consumed, 5);
+        assert_eq!(block.data, vec![0x64]);
+    }
+
+    #[test]
+    fn zero_fill_returns_none_if_source_is_empty() {
+        let cmd_config = CommandConfiguration::new(0b011, 3, 1024, false);
+        let source = &[];
+        let history_table = HistoryTable::new(source);
+        let block = zero_fill(&cmd_config, source, 0, &history_table);
+        assert!(block.is_none());
+    }
+
+    #[test]
+    fn signed_repeat_works() {
+        let cmd_config = CommandConfiguration::new(0b100, 3, 1024, false);
+        let source = b"ASDF_APPLEAPPLE";
+        let mut history_table = HistoryTable::new(source);
+        history_table.insert(b'A', b'P', 5);
+        let block = signed_repeat(&cmd_config, &source[10..], 10, &history_table).unwrap();
+        assert_eq!(block.index, 10);
+        assert_eq!(block.num_bytes_consumed, 5);
+        assert_eq!(block.data, vec![0x84, 0x84]);
+    }
+
+    #[test]
+    fn signed_repeat_returns_none_if_source_is_empty() {
+        let cmd_config = CommandConfiguration::new(0b100, 3, 1024, false);
+        let source = &[];
+        let history_table = HistoryTable::new(source);
+        let block = signed_repeat(&cmd_config, source, 0, &history_table);
+        assert!(block.is_none());
+    }
+
+    #[test]
+    fn signed_bit_reversed_repeat_works() {
+        let cmd_config = CommandConfiguration::new(0b101, 3, 1024, false);
+        let mut source: Vec<u8> = b"ASDF_APPLE".to_vec();
+        source.append(&mut b"APPLE".iter().map(|&x| x.reverse_bits()).collect());
+        let mut history_table = HistoryTable::new(&source);
+        history_table.insert(b'A', b'P', 5);
+        let block =
+            signed_bit_reversed_repeat(&cmd_config, &source[10..], 10, &history_table).unwrap();
+        assert_eq!(block.index, 10);
+        assert_eq!(block.num_bytes_consumed, 5);
+        assert_eq!(block.data, vec![0xA4, 0x84]);
+    }
+
+    #[test]
+    fn signed_bit_reversed_repeat_returns_none_if_source_is_empty() {
+        let cmd_config = CommandConfiguration::new(0b101, 3, 1024, false);
+        let source = &[];
+        let history_table = HistoryTable::new(source);
+        let block = signed_bit_reversed_repeat(&cmd_config, source, 0, &history_table);
+        assert!(block.is_none());
+    }
+
+    #[test]
+    fn signed_backwards_repeat_works() {
+        let cmd_config = CommandConfiguration::new(0b110, 3, 1024, false);
+        let source = b"ASDF_APPLEELPPA";
+        let mut history_table = HistoryTable::new(source);
+        history_table.insert(b'L', b'E', 8);
+        let block =
+            signed_backwards_repeat(&cmd_config, &source[10..], 10, &history_table).unwrap();
+        assert_eq!(block.index, 10);
+        assert_eq!(block.num_bytes_consumed, 5);
+        assert_eq!(block.data, vec![0xC4, 0x80]);
+    }
+
+    #[test]
+    fn signed_backwards_repeat_returns_none_if_source_is_empty() {
+        let cmd_config = CommandConfiguration::new(0b110, 3, 1024, false);
+        let source = &[];
+        let history_table = HistoryTable::new(source);
+        let block = signed_backwards_repeat(&cmd_config, source, 0, &history_table);
+        assert!(block.is_none());
+    }
 }
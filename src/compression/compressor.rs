@@ -0,0 +1,291 @@
+use std::io::{self, Write};
+
+use super::greedy::GreedyParser;
+use super::history_table::{HistoryState, HistoryTable};
+use super::options::{CompressionLevel, CompressionOptions};
+use super::strategies::{self, CompressionStrategy};
+use super::{compress_with_options, frame_stream, verify_with_framing};
+use crate::errors::CompressionError;
+use crate::{CompressionType, Framing};
+
+/// Compresses a stream as it is written.
+///
+/// A block is written to `destination` as soon as enough data has arrived after it to settle it,
+/// so the output is the same as [`compress_with_options`](crate::compress_with_options) with the
+/// same options. The terminator is written by [`finish`](Compressor::finish), and dropping a
+/// `Compressor` without finishing leaves the stream incomplete.
+///
+/// Every LZ format has commands with absolute offsets, which can refer back to any earlier byte,
+/// so all the uncompressed data is kept until `finish`. Memory grows with the input, just as with
+/// [`compress`](crate::compress), which takes the whole input at once.
+///
+/// The optimal level weighs the whole source at once and the RLE formats split it into planes,
+/// so with those nothing is written until `finish`. The same goes for [`Framing::SizePrefix`],
+/// since the size is only known at the end.
+pub struct Compressor<W: Write> {
+    destination: W,
+    compression_type: CompressionType,
+    options: CompressionOptions,
+    strategy: Option<CompressionStrategy>,
+    source: Vec<u8>,
+    history_state: Option<HistoryState>,
+    parser: GreedyParser,
+    buffer: Vec<u8>,
+    written: Vec<u8>,
+}
+
+impl<W: Write> Compressor<W> {
+    pub fn new(destination: W, compression_type: CompressionType) -> Self {
+        Self::with_options(destination, compression_type, CompressionOptions::default())
+    }
+
+    pub fn with_options(
+        destination: W,
+        compression_type: CompressionType,
+        options: CompressionOptions,
+    ) -> Self {
+        let strategy = match options.level {
+            CompressionLevel::Optimal => None,
+            _ => strategies::get_compression_strategy(compression_type, &options),
+        };
+        Compressor {
+            destination,
+            compression_type,
+            options,
+            strategy,
+            source: Vec::new(),
+            history_state: None,
+            parser: GreedyParser::new(&options),
+            buffer: Vec::new(),
+            written: Vec::new(),
+        }
+    }
+
+    /// Compresses the rest of the data, writes the terminator, and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.finish_buffer().map_err(into_io_error)?;
+        self.destination.write_all(&self.buffer)?;
+        Ok(self.destination)
+    }
+
+    fn finish_buffer(&mut self) -> Result<(), CompressionError> {
+        let Some(strategy) = &self.strategy else {
+            self.buffer =
+                compress_with_options(&self.source, self.compression_type, &self.options)?;
+            return Ok(());
+        };
+        let mut history_table =
+            get_history_table(&self.source, &mut self.history_state, &self.options);
+        let end_index = self.source.len();
+        self.parser.parse_until(
+            &self.source,
+            strategy,
+            &mut history_table,
+            end_index,
+            &mut self.buffer,
+        )?;
+        self.parser
+            .finish(&self.source, strategy, &history_table, &mut self.buffer)?;
+        self.buffer.push(strategy.terminator());
+        let buffer = std::mem::take(&mut self.buffer);
+        self.buffer = frame_stream(buffer, self.source.len(), &self.options)?;
+        if self.options.verify {
+            self.written.extend_from_slice(&self.buffer);
+            let framing = self.options.framing;
+            verify_with_framing(&self.source, &self.written, &self.compression_type, framing)?;
+        }
+        Ok(())
+    }
+
+    /// Decides every block that no longer depends on data still to come.
+    fn parse_available(&mut self) -> Result<(), CompressionError> {
+        let Some(strategy) = &self.strategy else {
+            return Ok(());
+        };
+        let lookahead = strategy.max_block_size() + 1;
+        let end_index = self.source.len().saturating_sub(lookahead);
+        let mut history_table =
+            get_history_table(&self.source, &mut self.history_state, &self.options);
+        let result = self.parser.parse_until(
+            &self.source,
+            strategy,
+            &mut history_table,
+            end_index,
+            &mut self.buffer,
+        );
+        self.history_state = Some(history_table.into_state());
+        result
+    }
+
+    fn write_buffer(&mut self) -> io::Result<()> {
+        if self.options.framing == Framing::SizePrefix {
+            return Ok(());
+        }
+        self.destination.write_all(&self.buffer)?;
+        if self.options.verify {
+            self.written.extend_from_slice(&self.buffer);
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Compressor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.source.extend_from_slice(buf);
+        self.parse_available().map_err(into_io_error)?;
+        self.write_buffer()?;
+        Ok(buf.len())
+    }
+
+    /// Flushes the underlying writer. Blocks that still depend on data to come are not written.
+    fn flush(&mut self) -> io::Result<()> {
+        self.destination.flush()
+    }
+}
+
+/// Rebuilds the history table over the data received so far, continuing from `history_state`.
+fn get_history_table<'a>(
+    source: &'a [u8],
+    history_state: &mut Option<HistoryState>,
+    options: &CompressionOptions,
+) -> HistoryTable<'a> {
+    let history_state = history_state.take().unwrap_or_default();
+    HistoryTable::from_state(source, history_state).set_search_depth(options.search_depth)
+}
+
+fn into_io_error(error: CompressionError) -> io::Error {
+    io::Error::other(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_test_data(size: usize) -> Vec<u8> {
+        let mut state: u32 = 0x12345678;
+        let mut data = Vec::new();
+        while data.len() < size {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            let byte = (state >> 24) as u8;
+            let run_size = (state >> 8) as usize % 12 + 1;
+            data.extend((0..run_size).map(|i| byte.wrapping_add((i as u8) & byte & 3)));
+        }
+        data.truncate(size);
+        data
+    }
+
+    fn compress_in_chunks(
+        source: &[u8],
+        compression_type: CompressionType,
+        options: CompressionOptions,
+        chunk_size: usize,
+    ) -> Vec<u8> {
+        let mut compressor = Compressor::with_options(Vec::new(), compression_type, options);
+        for chunk in source.chunks(chunk_size) {
+            compressor.write_all(chunk).unwrap();
+        }
+        compressor.finish().unwrap()
+    }
+
+    #[test]
+    fn compressor_matches_compress() {
+        let compression_types = [
+            CompressionType::LZ1,
+            CompressionType::LZ2,
+            CompressionType::LZ3,
+            CompressionType::LZ4,
+            CompressionType::LZ5,
+            CompressionType::LZ19,
+            CompressionType::RLE1,
+            CompressionType::RLE2,
+        ];
+        let levels = [
+            CompressionLevel::Fast,
+            CompressionLevel::Lazy,
+            CompressionLevel::Optimal,
+        ];
+        let source = get_test_data(0x1800);
+        for compression_type in compression_types {
+            for level in levels {
+                let options = CompressionOptions::new().set_level(level);
+                let expected = compress_with_options(&source, compression_type, &options).unwrap();
+                let chunk_sizes: &[usize] = match level {
+                    CompressionLevel::Optimal => &[7],
+                    _ => &[1, 7, 0x400, 0x1000],
+                };
+                for &chunk_size in chunk_sizes {
+                    let compressed =
+                        compress_in_chunks(&source, compression_type, options, chunk_size);
+                    assert_eq!(
+                        compressed, expected,
+                        "{compression_type} {level:?} {chunk_size}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compressor_writes_blocks_before_finish() {
+        let source = get_test_data(0x1000);
+        let mut compressor = Compressor::new(Vec::new(), CompressionType::LZ5);
+        compressor.write_all(&source).unwrap();
+        assert!(!compressor.destination.is_empty());
+        let compressed = compressor.finish().unwrap();
+        assert_eq!(
+            compressed,
+            crate::compress(&source, CompressionType::LZ5).unwrap()
+        );
+    }
+
+    #[test]
+    fn compressor_writes_terminator_for_empty_input() {
+        let compressor = Compressor::new(Vec::new(), CompressionType::LZ5);
+        assert_eq!(compressor.finish().unwrap(), vec![0xFF]);
+    }
+
+    #[test]
+    fn compressor_verifies_output() {
+        let source = get_test_data(0x1800);
+        let options = CompressionOptions::new().set_verify(true);
+        let compressed = compress_in_chunks(&source, CompressionType::LZ3, options, 0x100);
+        assert_eq!(
+            compressed,
+            crate::compress(&source, CompressionType::LZ3).unwrap()
+        );
+    }
+
+    #[test]
+    fn compressor_matches_compress_past_64_kib() {
+        let source = get_test_data(0x11000);
+        let expected = crate::compress(&source, CompressionType::LZ5).unwrap();
+        let options = CompressionOptions::default();
+        let compressed = compress_in_chunks(&source, CompressionType::LZ5, options, 0x1000);
+        assert!(compressed == expected);
+    }
+
+    #[test]
+    fn compressor_honors_framing() {
+        let source = get_test_data(0x1800);
+        for framing in [Framing::OutputSize(source.len()), Framing::SizePrefix] {
+            let options = CompressionOptions::new()
+                .set_framing(framing)
+                .set_verify(true);
+            let expected = compress_with_options(&source, CompressionType::LZ5, &options).unwrap();
+            let compressed = compress_in_chunks(&source, CompressionType::LZ5, options, 0x100);
+            assert_eq!(compressed, expected, "{framing:?}");
+        }
+    }
+
+    #[test]
+    fn compressor_holds_output_for_size_prefix() {
+        let source = get_test_data(0x1000);
+        let options = CompressionOptions::new().set_framing(Framing::SizePrefix);
+        let mut compressor = Compressor::with_options(Vec::new(), CompressionType::LZ5, options);
+        compressor.write_all(&source).unwrap();
+        assert!(compressor.destination.is_empty());
+        let compressed = compressor.finish().unwrap();
+        assert_eq!(compressed[..2], [0x00, 0x10]);
+    }
+}
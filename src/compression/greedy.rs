@@ -0,0 +1,116 @@
+use alloc::{vec, vec::Vec};
+
+use super::history_table::HistoryTable;
+use super::options::{CompressionLevel, CompressionOptions};
+use super::strategies::CompressionStrategy;
+use crate::errors::CompressionError;
+
+/// How far a greedy parse has gotten. Every position before `index` has been decided, and the
+/// bytes from `last_block_end_index` up to `index` are waiting to be written by the fallback
+/// command.
+pub struct GreedyParser {
+    index: usize,
+    last_block_end_index: usize,
+    is_lazy: bool,
+}
+
+impl GreedyParser {
+    pub fn new(options: &CompressionOptions) -> Self {
+        GreedyParser {
+            index: 0,
+            last_block_end_index: 0,
+            is_lazy: options.level == CompressionLevel::Lazy,
+        }
+    }
+
+    /// Decides every position before `end_index`, appending the finished blocks to `buffer`.
+    ///
+    /// Blocks starting at a position can use up to `max_block_size` bytes after it, and a lazy
+    /// parse also looks at the next position. As long as those bytes are in `source`, the blocks
+    /// are the same as when the whole source is available.
+    pub fn parse_until(
+        &mut self,
+        source: &[u8],
+        strategy: &CompressionStrategy,
+        history_table: &mut HistoryTable,
+        end_index: usize,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CompressionError> {
+        while self.index < end_index {
+            let i = self.index;
+            history_table.insert_until(i);
+
+            if i < self.last_block_end_index {
+                self.index += 1;
+                continue;
+            }
+
+            if let Some(block) = strategy.get_best_block(source, i, history_table) {
+                if self.is_lazy && i + 1 < source.len() {
+                    history_table.insert_until(i + 1);
+                    let next_block = strategy.get_best_block(source, i + 1, history_table);
+                    if next_block.is_some_and(|next| next.difference() > block.difference() + 1) {
+                        self.index += 1;
+                        continue;
+                    }
+                }
+                self.write_fallback_blocks(source, strategy, history_table, buffer)?;
+                self.last_block_end_index = i + block.num_bytes_consumed;
+                buffer.append(&mut block.collect());
+            }
+            self.index += 1;
+        }
+        Ok(())
+    }
+
+    /// Writes the bytes after the last block, once `source` is complete.
+    pub fn finish(
+        &mut self,
+        source: &[u8],
+        strategy: &CompressionStrategy,
+        history_table: &HistoryTable,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CompressionError> {
+        self.index = self.index.max(source.len());
+        self.write_fallback_blocks(source, strategy, history_table, buffer)
+    }
+
+    fn write_fallback_blocks(
+        &self,
+        source: &[u8],
+        strategy: &CompressionStrategy,
+        history_table: &HistoryTable,
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CompressionError> {
+        if self.index <= self.last_block_end_index {
+            return Ok(());
+        }
+        let fallback_blocks = strategy
+            .get_fallback_blocks(source, self.last_block_end_index, self.index, history_table)
+            .ok_or(CompressionError::CompressionFailed)?;
+        for fallback_block in fallback_blocks {
+            buffer.append(&mut fallback_block.collect());
+        }
+        Ok(())
+    }
+}
+
+pub fn compress_greedy(
+    source: &[u8],
+    strategy: &CompressionStrategy,
+    options: &CompressionOptions,
+) -> Result<Vec<u8>, CompressionError> {
+    let mut history_table = HistoryTable::new(source).set_search_depth(options.search_depth);
+    let mut parser = GreedyParser::new(options);
+    let mut buffer: Vec<u8> = vec![];
+    parser.parse_until(
+        source,
+        strategy,
+        &mut history_table,
+        source.len(),
+        &mut buffer,
+    )?;
+    parser.finish(source, strategy, &history_table, &mut buffer)?;
+    buffer.push(strategy.terminator());
+    Ok(buffer)
+}
@@ -1,6 +1,8 @@
-use std::collections::HashMap;
+use alloc::{vec, vec::Vec};
+use core::cmp;
 
-type WordMap = HashMap<u8, HashMap<u8, Vec<usize>>>;
+/// Marks the end of a chain in `heads` and `chains`.
+const NO_INDEX: usize = usize::MAX;
 
 pub struct RepeatInformation {
     pub start_index: usize,
@@ -13,37 +15,108 @@ impl RepeatInformation {
     }
 }
 
+/// Finds earlier occurrences of the data at a position.
+///
+/// Every inserted index is linked into a chain for the pair of bytes that starts there. `heads`
+/// and `tails` hold the most recent and the oldest index for each of the 65536 pairs, and
+/// `chains` and `next_chains` hold, for each index, the previous and the next index that starts
+/// with the same pair.
+///
+/// A search finds its oldest candidate by walking back from the head, stopping at its lower bound
+/// or its search depth, or starts at the tail when neither can be reached. It then walks forward,
+/// so the earliest of the longest matches is found first and a full match ends the search.
 pub struct HistoryTable<'a> {
     source: &'a [u8],
-    map: WordMap,
+    state: HistoryState,
+}
+
+/// The part of a [`HistoryTable`] that does not borrow the source. It can be taken out of a table
+/// and put back into a new one when the source grows, without inserting everything again.
+pub struct HistoryState {
+    heads: Vec<usize>,
+    tails: Vec<usize>,
+    /// The number of indices inserted for each pair.
+    counts: Vec<usize>,
+    chains: Vec<usize>,
+    next_chains: Vec<usize>,
+    search_depth: usize,
+    next_pair_index: usize,
+}
+
+impl Default for HistoryState {
+    fn default() -> Self {
+        HistoryState {
+            heads: vec![NO_INDEX; 0x10000],
+            tails: vec![NO_INDEX; 0x10000],
+            counts: vec![0; 0x10000],
+            chains: Vec::new(),
+            next_chains: Vec::new(),
+            search_depth: usize::MAX,
+            next_pair_index: 0,
+        }
+    }
 }
 
 impl HistoryTable<'_> {
-    pub fn new(source: &[u8]) -> HistoryTable {
-        HistoryTable {
-            map: HashMap::new(),
-            source,
+    pub fn new(source: &[u8]) -> HistoryTable<'_> {
+        let state = HistoryState {
+            chains: vec![NO_INDEX; source.len()],
+            next_chains: vec![NO_INDEX; source.len()],
+            ..Default::default()
+        };
+        HistoryTable { source, state }
+    }
+
+    /// Builds a table over `source` that continues from `state`. `source` must start with the
+    /// source that `state` was built from.
+    #[cfg(feature = "std")]
+    pub fn from_state(source: &[u8], state: HistoryState) -> HistoryTable<'_> {
+        HistoryTable { source, state }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn into_state(self) -> HistoryState {
+        self.state
+    }
+
+    /// Limits how many of the most recent matching positions each search looks at.
+    pub fn set_search_depth(mut self, search_depth: usize) -> Self {
+        self.state.search_depth = search_depth;
+        self
+    }
+
+    /// Inserts every pair of `source` that starts before `index` and has not been inserted yet.
+    pub fn insert_until(&mut self, index: usize) {
+        let end_index = cmp::min(index, self.source.len().saturating_sub(1));
+        while self.state.next_pair_index < end_index {
+            let i = self.state.next_pair_index;
+            self.insert(self.source[i], self.source[i + 1], i);
+            self.state.next_pair_index += 1;
         }
     }
 
+    /// Inserts the pair `first`, `second` starting at `index`. Indices must be inserted in
+    /// increasing order, and an index that is not past the most recent one for the pair is
+    /// ignored, so a chain can never link back to itself.
     pub fn insert(&mut self, first: u8, second: u8, index: usize) {
-        let internal_map = match self.map.get_mut(&first) {
-            Some(internal_map) => internal_map,
-            None => {
-                let internal_map = HashMap::new();
-                self.map.insert(first, internal_map);
-                self.map.get_mut(&first).unwrap()
-            }
-        };
-        let index_collection = match internal_map.get_mut(&second) {
-            Some(index_collection) => index_collection,
-            None => {
-                let index_collection = Vec::new();
-                internal_map.insert(second, index_collection);
-                internal_map.get_mut(&second).unwrap()
-            }
-        };
-        index_collection.push(index);
+        let state = &mut self.state;
+        let key = get_key(first, second);
+        let head = state.heads[key];
+        if head != NO_INDEX && head >= index {
+            return;
+        }
+        if index >= state.chains.len() {
+            state.chains.resize(index + 1, NO_INDEX);
+            state.next_chains.resize(index + 1, NO_INDEX);
+        }
+        state.chains[index] = head;
+        state.next_chains[index] = NO_INDEX;
+        match head {
+            NO_INDEX => state.tails[key] = index,
+            _ => state.next_chains[head] = index,
+        }
+        state.heads[key] = index;
+        state.counts[key] += 1;
     }
 
     pub fn find_longest_repeat(
@@ -53,34 +126,10 @@ impl HistoryTable<'_> {
     ) -> Option<RepeatInformation> {
         let &first_byte = source.get(0)?;
         let &second_byte = source.get(1)?;
-        let indices = self.get_indices(first_byte, second_byte, lower_bound)?;
-
-        let mut best_index = 0;
-        let mut best_size = 0;
-        indices.iter().for_each(|x| {
-            let mut iter_index = *x;
-            let mut compare_index = 0;
-            let mut num_same = 0;
-            loop {
-                let a = self.source.get(iter_index);
-                let b = source.get(compare_index);
-                if a.is_none() || b.is_none() || a != b {
-                    if num_same > best_size {
-                        best_index = *x;
-                        best_size = num_same;
-                    }
-                    break;
-                }
-                num_same += 1;
-                iter_index += 1;
-                compare_index += 1;
-            }
-        });
-        if best_size == 0 {
-            None
-        } else {
-            Some(RepeatInformation::new(best_index, best_size))
-        }
+        let indices = self.get_indices(first_byte, second_byte, lower_bound);
+        self.find_longest_match(source, indices, |x, offset| {
+            self.source.get(x + offset).copied()
+        })
     }
 
     pub fn find_longest_repeat_xor(
@@ -92,41 +141,83 @@ impl HistoryTable<'_> {
         let &second_byte = source.get(1)?;
         let first_byte = first_byte ^ 0xFF;
         let second_byte = second_byte ^ 0xFF;
-        let indices = self.get_indices(first_byte, second_byte, lower_bound)?;
+        let indices = self.get_indices(first_byte, second_byte, lower_bound);
+        self.find_longest_match(source, indices, |x, offset| {
+            self.source.get(x + offset).map(|byte| byte ^ 0xFF)
+        })
+    }
+
+    pub fn find_longest_repeat_bit_reversed(
+        &self,
+        source: &[u8],
+        lower_bound: usize,
+    ) -> Option<RepeatInformation> {
+        let &first_byte = source.first()?;
+        let &second_byte = source.get(1)?;
+        let first_byte = first_byte.reverse_bits();
+        let second_byte = second_byte.reverse_bits();
+        let indices = self.get_indices(first_byte, second_byte, lower_bound);
+        self.find_longest_match(source, indices, |x, offset| {
+            self.source.get(x + offset).map(|byte| byte.reverse_bits())
+        })
+    }
+
+    /// Finds the longest run that matches `source` when read backwards from its start index.
+    /// Only start indices in `lower_bound..index` are considered.
+    pub fn find_longest_repeat_reversed(
+        &self,
+        source: &[u8],
+        index: usize,
+        lower_bound: usize,
+    ) -> Option<RepeatInformation> {
+        let &first_byte = source.first()?;
+        let &second_byte = source.get(1)?;
+        let lower_bound = lower_bound.saturating_sub(1);
+        let indices = self
+            .get_indices(second_byte, first_byte, lower_bound)
+            .map(|x| x + 1)
+            .filter(|&x| x < index);
+        self.find_longest_match(source, indices, |x, offset| {
+            let iter_index = x.checked_sub(offset)?;
+            self.source.get(iter_index).copied()
+        })
+    }
 
+    /// Compares `source` against the history at each of `indices`, keeping the longest match.
+    /// Ties go to the first index, and the search stops early once nothing longer is possible.
+    fn find_longest_match<I, F>(
+        &self,
+        source: &[u8],
+        indices: I,
+        get_history_byte: F,
+    ) -> Option<RepeatInformation>
+    where
+        I: Iterator<Item = usize>,
+        F: Fn(usize, usize) -> Option<u8>,
+    {
         let mut best_index = 0;
         let mut best_size = 0;
-        indices.iter().for_each(|x| {
-            let mut iter_index = *x;
-            let mut compare_index = 0;
+        for x in indices {
+            // Only a match longer than the best one matters, so a mismatch at its end rules the
+            // index out without comparing the bytes before it.
+            if best_size > 0 && get_history_byte(x, best_size) != Some(source[best_size]) {
+                continue;
+            }
             let mut num_same = 0;
-            loop {
-                let a = self.source.get(iter_index);
-                let b = source.get(compare_index);
-
-                if a.is_none() || b.is_none() {
-                    if num_same > best_size {
-                        best_index = *x;
-                        best_size = num_same;
-                    }
-                    break;
-                }
-
-                let a = a.unwrap() ^ 0xFF;
-                let b = b.unwrap().to_owned();
-                if a != b {
-                    if num_same > best_size {
-                        best_index = *x;
-                        best_size = num_same;
-                    }
+            while let Some(byte) = source.get(num_same) {
+                if get_history_byte(x, num_same) != Some(*byte) {
                     break;
                 }
-
                 num_same += 1;
-                iter_index += 1;
-                compare_index += 1;
             }
-        });
+            if num_same > best_size {
+                best_index = x;
+                best_size = num_same;
+            }
+            if best_size == source.len() {
+                break;
+            }
+        }
         if best_size == 0 {
             None
         } else {
@@ -134,18 +225,90 @@ impl HistoryTable<'_> {
         }
     }
 
-    fn get_indices(&self, first: u8, second: u8, lower_bound: usize) -> Option<Vec<usize>> {
-        let internal_map = self.map.get(&first)?;
-        let indices = internal_map.get(&second)?;
-        if indices.is_empty() {
-            None
-        } else {
-            let indices = indices
-                .iter()
-                .filter(|&&x| x >= lower_bound)
-                .map(|&x| x)
-                .collect();
-            Some(indices)
-        }
+    /// Walks the chain for `first`, `second` from the oldest index that is at least
+    /// `lower_bound` and among the `search_depth` most recent ones, up to the most recent index.
+    fn get_indices(
+        &self,
+        first: u8,
+        second: u8,
+        lower_bound: usize,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let state = &self.state;
+        let key = get_key(first, second);
+        let head = state.heads[key];
+        let tail = state.tails[key];
+        let start =
+            if tail != NO_INDEX && tail >= lower_bound && state.counts[key] <= state.search_depth {
+                Some(tail)
+            } else {
+                core::iter::successors(Some(head), |&x| state.chains.get(x).copied())
+                    .take_while(|&x| x != NO_INDEX && x >= lower_bound)
+                    .take(state.search_depth)
+                    .last()
+            };
+        core::iter::successors(start, move |&x| {
+            if x == head {
+                None
+            } else {
+                state.next_chains.get(x).copied()
+            }
+        })
+    }
+}
+
+fn get_key(first: u8, second: u8) -> usize {
+    ((first as usize) << 8) | second as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_longest_repeat_prefers_earliest_index_on_ties() {
+        let source = [0x12, 0x34, 0x56, 0x12, 0x34, 0x56, 0x12, 0x34, 0x78];
+        let mut history_table = HistoryTable::new(&source);
+        history_table.insert_until(6);
+        let repeat = history_table.find_longest_repeat(&[0x12, 0x34], 0).unwrap();
+        assert_eq!(repeat.start_index, 0);
+        assert_eq!(repeat.size, 2);
+        let repeat = history_table.find_longest_repeat(&[0x12, 0x34], 1).unwrap();
+        assert_eq!(repeat.start_index, 3);
+        assert_eq!(repeat.size, 2);
+    }
+
+    #[test]
+    fn insert_ignores_indices_already_in_the_chain() {
+        let source = [0x12, 0x34, 0x56, 0x12, 0x34, 0x78];
+        let mut history_table = HistoryTable::new(&source);
+        history_table.insert_until(4);
+        history_table.insert(0x12, 0x34, 3);
+        history_table.insert(0x12, 0x34, 0);
+        let indices: Vec<usize> = history_table.get_indices(0x12, 0x34, 0).collect();
+        assert_eq!(indices, vec![0, 3]);
+    }
+
+    #[test]
+    fn find_longest_repeat_respects_lower_bound() {
+        let source = [0x12, 0x34, 0x56, 0x12, 0x34, 0x78, 0x12, 0x34, 0x56];
+        let mut history_table = HistoryTable::new(&source);
+        history_table.insert_until(6);
+        let repeat = history_table.find_longest_repeat(&source[6..], 0).unwrap();
+        assert_eq!(repeat.start_index, 0);
+        assert_eq!(repeat.size, 3);
+        let repeat = history_table.find_longest_repeat(&source[6..], 1).unwrap();
+        assert_eq!(repeat.start_index, 3);
+        assert_eq!(repeat.size, 2);
+        assert!(history_table.find_longest_repeat(&source[6..], 4).is_none());
+    }
+
+    #[test]
+    fn set_search_depth_limits_candidates_to_most_recent() {
+        let source = [0x12, 0x34, 0x56, 0x12, 0x34, 0x78, 0x12, 0x34, 0x56];
+        let mut history_table = HistoryTable::new(&source).set_search_depth(1);
+        history_table.insert_until(6);
+        let repeat = history_table.find_longest_repeat(&source[6..], 0).unwrap();
+        assert_eq!(repeat.start_index, 3);
+        assert_eq!(repeat.size, 2);
     }
 }
@@ -1,74 +1,346 @@
 mod block;
 mod command;
-mod command_callbacks;
+pub mod command_callbacks;
+#[cfg(feature = "std")]
+mod compressor;
+mod greedy;
 mod history_table;
+mod optimal;
+mod options;
+mod rle;
 mod strategies;
 
-use self::block::Block;
+use alloc::{string::ToString, vec::Vec};
+use core::cmp;
 
 use super::CompressionType;
-use crate::errors::CompressionError;
-use history_table::HistoryTable;
-use strategies::CompressionStrategy;
+use crate::errors::{CompressionError, DecompressionErrorInfo};
+use crate::framing::{self, Framing};
+use crate::{Codec, DecompressionOptions};
+
+pub use command::CommandConfiguration;
+#[cfg(feature = "std")]
+pub use compressor::Compressor;
+pub use options::{CompressionLevel, CompressionOptions};
+pub use strategies::CompressionStrategy;
 
 pub fn compress(
     source: &[u8],
     compression_type: CompressionType,
 ) -> Result<Vec<u8>, CompressionError> {
-    let strategy = strategies::get_compression_strategy(compression_type).ok_or(
+    compress_with_options(source, compression_type, &CompressionOptions::default())
+}
+
+/// Compresses `source` like [`compress`], trading speed for output size according to `level`.
+pub fn compress_with_level(
+    source: &[u8],
+    compression_type: CompressionType,
+    level: CompressionLevel,
+) -> Result<Vec<u8>, CompressionError> {
+    let options = CompressionOptions::new().set_level(level);
+    compress_with_options(source, compression_type, &options)
+}
+
+/// Compresses `source` like [`compress`], then decompresses the output to make sure it matches.
+pub fn compress_verified(
+    source: &[u8],
+    compression_type: CompressionType,
+) -> Result<Vec<u8>, CompressionError> {
+    let options = CompressionOptions::new().set_verify(true);
+    compress_with_options(source, compression_type, &options)
+}
+
+/// Compresses `source` like [`compress`], trading speed for output size according to `options`.
+/// The RLE formats have only one way to compress, so they ignore every option except `verify` and
+/// `framing`. RLE2 ends each of its planes with a terminator, so it only supports
+/// [`Framing::Terminator`].
+pub fn compress_with_options(
+    source: &[u8],
+    compression_type: CompressionType,
+    options: &CompressionOptions,
+) -> Result<Vec<u8>, CompressionError> {
+    let compressed = match compression_type {
+        CompressionType::RLE1 => frame_stream(rle::compress_rle1(source), source.len(), options)?,
+        CompressionType::RLE2 if options.framing != Framing::Terminator => {
+            return Err(CompressionError::UnsupportedFraming {
+                compression_type: compression_type.to_string(),
+            })
+        }
+        CompressionType::RLE2 => rle::compress_rle2(source),
+        _ => compress_commands(source, compression_type, options)?,
+    };
+    if options.verify {
+        verify_with_framing(source, &compressed, &compression_type, options.framing)?;
+    }
+    Ok(compressed)
+}
+
+/// Checks that `compressed` decompresses to `source` with `codec`, reporting the first offset that
+/// differs.
+pub fn verify(source: &[u8], compressed: &[u8], codec: &dyn Codec) -> Result<(), CompressionError> {
+    verify_with_framing(source, compressed, codec, Framing::Terminator)
+}
+
+/// Checks `compressed` like [`verify`], for a stream framed with `framing`. For
+/// [`Framing::OutputSize`], the length of `source` is used as the size.
+pub fn verify_with_framing(
+    source: &[u8],
+    compressed: &[u8],
+    codec: &dyn Codec,
+    framing: Framing,
+) -> Result<(), CompressionError> {
+    let framing = match framing {
+        Framing::OutputSize(_) => Framing::OutputSize(source.len()),
+        _ => framing,
+    };
+    let options = DecompressionOptions::new()
+        .set_error_buffers(true)
+        .set_framing(framing);
+    let decompressed = codec.decompress(compressed, &options);
+    let result = match &decompressed {
+        Ok(decompressed) => decompressed.as_slice(),
+        Err(error) => error
+            .error_info()
+            .map_or(&[][..], DecompressionErrorInfo::result),
+    };
+    let mismatch = source.iter().zip(result).position(|(a, b)| a != b);
+    let offset = match mismatch {
+        Some(offset) => offset,
+        None if decompressed.is_ok() && source.len() == result.len() => return Ok(()),
+        None => cmp::min(source.len(), result.len()),
+    };
+    Err(CompressionError::VerificationFailed { offset })
+}
+
+fn compress_commands(
+    source: &[u8],
+    compression_type: CompressionType,
+    options: &CompressionOptions,
+) -> Result<Vec<u8>, CompressionError> {
+    let strategy = strategies::get_compression_strategy(compression_type, options).ok_or(
         CompressionError::UnsupportedFormat {
             compression_type: compression_type.to_string(),
         },
     )?;
+    compress_with_strategy(source, &strategy, options)
+}
+
+/// Compresses `source` with the commands in `strategy`, for formats that are not built in.
+///
+/// The strategy decides which commands are used, so `use_increasing_fill` and `use_xor_commands`
+/// are ignored. Checking the output needs a decompressor as well, so `verify` is ignored too;
+/// [`LzCodec`](crate::LzCodec) pairs the two and honors it.
+pub fn compress_with_strategy(
+    source: &[u8],
+    strategy: &CompressionStrategy,
+    options: &CompressionOptions,
+) -> Result<Vec<u8>, CompressionError> {
+    let compressed = match options.level {
+        CompressionLevel::Fast | CompressionLevel::Lazy => {
+            greedy::compress_greedy(source, strategy, options)?
+        }
+        CompressionLevel::Optimal => optimal::compress_optimal(source, strategy, options)?,
+    };
+    frame_stream(compressed, source.len(), options)
+}
+
+/// Replaces the terminator at the end of `compressed` with the framing in `options`.
+fn frame_stream(
+    mut compressed: Vec<u8>,
+    source_len: usize,
+    options: &CompressionOptions,
+) -> Result<Vec<u8>, CompressionError> {
+    match options.framing {
+        Framing::Terminator => {}
+        Framing::OutputSize(_) => {
+            compressed.pop();
+        }
+        Framing::SizePrefix => {
+            let size_prefix = framing::build_size_prefix(source_len)
+                .ok_or(CompressionError::SourceTooLarge { size: source_len })?;
+            compressed.pop();
+            compressed.splice(0..0, size_prefix);
+        }
+    }
+    Ok(compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_accepts_matching_output() {
+        let source = [0xA, 0xA, 0xA, 0xA, 1, 2, 3, 4];
+        let compressed = [0x23, 0xA, 0x03, 1, 2, 3, 4, 0xFF];
+        assert_eq!(verify(&source, &compressed, &CompressionType::LZ5), Ok(()));
+    }
+
+    #[test]
+    fn verify_reports_first_mismatching_offset() {
+        let source = [0xA, 0xA, 0xA, 0xA, 1, 2, 3, 4];
+        let compressed = [0x23, 0xA, 0x03, 1, 2, 5, 4, 0xFF];
+        let expected = CompressionError::VerificationFailed { offset: 6 };
+        assert_eq!(
+            verify(&source, &compressed, &CompressionType::LZ5),
+            Err(expected)
+        );
+    }
+
+    #[test]
+    fn verify_reports_truncated_output() {
+        let source = [0xA, 0xA, 0xA, 0xA, 1, 2, 3, 4];
+        let compressed = [0x23, 0xA, 0x03, 1, 2];
+        let expected = CompressionError::VerificationFailed { offset: 4 };
+        assert_eq!(
+            verify(&source, &compressed, &CompressionType::LZ5),
+            Err(expected)
+        );
+    }
+
+    #[test]
+    fn verify_reports_extra_output() {
+        let source = [0xA, 0xA, 0xA, 0xA];
+        let compressed = [0x23, 0xA, 0x00, 1, 0xFF];
+        let expected = CompressionError::VerificationFailed { offset: 4 };
+        assert_eq!(
+            verify(&source, &compressed, &CompressionType::LZ5),
+            Err(expected)
+        );
+    }
+
+    #[test]
+    fn long_commands_do_not_end_the_stream() {
+        // A negative XOR repeat longer than 0x300 bytes would have a header starting with 0xFF.
+        let mut source = vec![0x12, 0x34, 0x56];
+        for i in 3..1000 {
+            source.push(source[i - 3] ^ 0xFF);
+        }
+        for compression_type in [CompressionType::LZ4, CompressionType::LZ5] {
+            for level in [
+                CompressionLevel::Fast,
+                CompressionLevel::Lazy,
+                CompressionLevel::Optimal,
+            ] {
+                let options = CompressionOptions::new().set_level(level).set_verify(true);
+                assert!(compress_with_options(&source, compression_type, &options).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn strategy_sizes_are_clamped_to_the_header() {
+        use crate::{compression_commands as comp, decompression_commands as decomp};
 
-    let mut history_table = HistoryTable::new(source);
-    let mut buffer: Vec<u8> = vec![];
-    let mut last_block_end_index = 0;
-    let mut i = 0;
-    while i < source.len() {
-        if i > 0 {
-            let first = source[i - 1];
-            let second = source[i];
-            history_table.insert(first, second, i - 1);
+        let source: Vec<u8> = (0..3000_u32).map(|i| (i * i / 7) as u8).collect();
+        for (cmd_size, max_block_size, expected) in [
+            (9, 1024, (4, 256)),
+            (0, 1024, (1, 1024)),
+            (3, 5000, (3, 1024)),
+            (3, 0, (3, 1)),
+        ] {
+            let strategy = CompressionStrategy::new(cmd_size, max_block_size).set_fallback_command(
+                0,
+                false,
+                comp::direct_copy,
+            );
+            assert_eq!(strategy.max_block_size(), expected.1);
+            let decompression_strategy = crate::DecompressionStrategy::new()
+                .set_num_cmd_bits(expected.0)
+                .insert_command(0, false, decomp::direct_copy);
+            let options = CompressionOptions::default();
+            let compressed = compress_with_strategy(&source, &strategy, &options).unwrap();
+            let decompressed = crate::decompress_with_strategy(
+                &compressed,
+                &decompression_strategy,
+                &DecompressionOptions::default(),
+            )
+            .unwrap();
+            assert_eq!(decompressed, source);
         }
+    }
+
+    #[test]
+    fn commands_that_save_nothing_do_not_panic() {
+        use crate::compression_commands as comp;
 
-        if i < last_block_end_index {
-            i += 1;
-            continue;
+        // A direct copy as a regular command writes more bytes than it consumes.
+        let strategy = CompressionStrategy::new(3, 1024)
+            .set_fallback_command(0b000, false, comp::direct_copy)
+            .insert_command(0b001, false, comp::direct_copy);
+        let source = [1, 2, 3, 4, 5];
+        for level in [
+            CompressionLevel::Fast,
+            CompressionLevel::Lazy,
+            CompressionLevel::Optimal,
+        ] {
+            let options = CompressionOptions::new().set_level(level);
+            let compressed = compress_with_strategy(&source, &strategy, &options).unwrap();
+            assert_eq!(compressed.len(), 1 + source.len() + 1, "{level:?}");
         }
+    }
 
-        if let Some(block) = strategy.get_best_block(source, i, &history_table) {
-            if i > last_block_end_index {
-                let fallback_blocks =
-                    get_fallback_blocks(&strategy, source, last_block_end_index, i, &history_table)?;
-                for fallback_block in fallback_blocks {
-                    buffer.append(&mut fallback_block.collect());
+    #[test]
+    fn framing_replaces_terminator() {
+        let source = [0xA, 0xA, 0xA, 0xA];
+        let cases = [
+            (Framing::Terminator, vec![0x23, 0xA, 0xFF]),
+            (Framing::OutputSize(4), vec![0x23, 0xA]),
+            (Framing::SizePrefix, vec![0x04, 0x00, 0x23, 0xA]),
+        ];
+        for (framing, expected) in cases {
+            let options = CompressionOptions::new().set_framing(framing);
+            let compressed = compress_with_options(&source, CompressionType::LZ5, &options);
+            assert_eq!(compressed, Ok(expected));
+        }
+    }
+
+    #[test]
+    fn framing_round_trips() {
+        let mut source: Vec<u8> = (0..0x300_u32).map(|i| (i * i / 7) as u8).collect();
+        source.extend([0xFF; 0x100]);
+        let compression_types = [
+            CompressionType::LZ2,
+            CompressionType::LZ5,
+            CompressionType::RLE1,
+        ];
+        let framings = [Framing::OutputSize(source.len()), Framing::SizePrefix];
+        for compression_type in compression_types {
+            for framing in framings {
+                for level in [CompressionLevel::Fast, CompressionLevel::Optimal] {
+                    let options = CompressionOptions::new()
+                        .set_level(level)
+                        .set_framing(framing)
+                        .set_verify(true);
+                    let compressed =
+                        compress_with_options(&source, compression_type, &options).unwrap();
+                    let options = DecompressionOptions::new().set_framing(framing);
+                    let decompressed =
+                        crate::decompress_with_options(&compressed, compression_type, &options);
+                    assert_eq!(decompressed, Ok(source.clone()));
                 }
             }
-            last_block_end_index = i + block.num_bytes_consumed;
-            buffer.append(&mut block.collect());
         }
-        i += 1;
     }
-    if i > last_block_end_index {
-        let fallback_blocks =
-            get_fallback_blocks(&strategy, source, last_block_end_index, i, &history_table)?;
-        for fallback_block in fallback_blocks {
-            buffer.append(&mut fallback_block.collect());
-        }
+
+    #[test]
+    fn size_prefix_framing_rejects_large_source() {
+        let source = vec![0; 0x10000];
+        let options = CompressionOptions::new().set_framing(Framing::SizePrefix);
+        let result = compress_with_options(&source, CompressionType::LZ5, &options);
+        assert_eq!(
+            result,
+            Err(CompressionError::SourceTooLarge { size: 0x10000 })
+        );
     }
-    buffer.push(0xFF);
-    Ok(buffer)
-}
 
-fn get_fallback_blocks(
-    strategy: &CompressionStrategy,
-    source: &[u8],
-    start_index: usize,
-    end_index: usize,
-    history_table: &HistoryTable,
-) -> Result<Vec<Block>, CompressionError> {
-    strategy
-        .get_fallback_blocks(source, start_index, end_index, history_table)
-        .ok_or(CompressionError::CompressionFailed)
+    #[test]
+    fn rle2_only_supports_terminator_framing() {
+        let options = CompressionOptions::new().set_framing(Framing::OutputSize(4));
+        let result = compress_with_options(&[1, 2, 3, 4], CompressionType::RLE2, &options);
+        let expected = CompressionError::UnsupportedFraming {
+            compression_type: "RLE2".to_string(),
+        };
+        assert_eq!(result, Err(expected));
+    }
 }
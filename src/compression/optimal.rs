@@ -0,0 +1,305 @@
+use alloc::{vec, vec::Vec};
+use core::cmp;
+
+use super::block::Block;
+use super::command::Command;
+use super::history_table::HistoryTable;
+use super::options::CompressionOptions;
+use super::strategies::{get_source_slice, CompressionStrategy};
+use crate::errors::CompressionError;
+
+#[derive(Clone, Copy)]
+struct Choice {
+    start_index: usize,
+    size: usize,
+    /// The index of the command in the strategy, or `None` for the fallback command.
+    command_idx: Option<usize>,
+    /// The length of the source the command was called with. The block it returns is cut down to
+    /// `size`.
+    source_size: usize,
+}
+
+impl Choice {
+    fn new(
+        start_index: usize,
+        size: usize,
+        command_idx: Option<usize>,
+        source_size: usize,
+    ) -> Self {
+        Choice {
+            start_index,
+            size,
+            command_idx,
+            source_size,
+        }
+    }
+
+    fn end_index(&self) -> usize {
+        self.start_index + self.size
+    }
+}
+
+/// Compresses `source` into the smallest stream the strategy's commands can produce.
+///
+/// At every position, each command is asked for its longest block, then for the longest blocks
+/// with fewer arguments, like an LZ3 one byte relative offset in place of a two byte absolute one.
+/// Each of those blocks can also be cut short, keeping its arguments, and the cheapest path from
+/// the start of `source` to the end is kept.
+pub fn compress_optimal(
+    source: &[u8],
+    strategy: &CompressionStrategy,
+    options: &CompressionOptions,
+) -> Result<Vec<u8>, CompressionError> {
+    let choices = find_cheapest_choices(source, strategy, options)?;
+
+    let mut history_table = HistoryTable::new(source).set_search_depth(options.search_depth);
+    let mut buffer: Vec<u8> = vec![];
+    for choice in choices {
+        history_table.insert_until(choice.start_index);
+
+        let command = match choice.command_idx {
+            Some(command_idx) => &strategy.commands()[command_idx],
+            None => strategy
+                .fallback_command()
+                .ok_or(CompressionError::CompressionFailed)?,
+        };
+        let block_source = &source[choice.start_index..choice.start_index + choice.source_size];
+        let block = command
+            .call(block_source, choice.start_index, &history_table)
+            .filter(|block| block.num_bytes_consumed >= choice.size)
+            .ok_or(CompressionError::CompressionFailed)?;
+        if block.num_bytes_consumed == choice.size {
+            buffer.append(&mut block.collect());
+        } else {
+            let header_size = command.header_size(block.num_bytes_consumed);
+            buffer.append(&mut command.header(choice.size));
+            buffer.extend_from_slice(&block.data[header_size..]);
+        }
+    }
+    buffer.push(strategy.terminator());
+    Ok(buffer)
+}
+
+fn find_cheapest_choices(
+    source: &[u8],
+    strategy: &CompressionStrategy,
+    options: &CompressionOptions,
+) -> Result<Vec<Choice>, CompressionError> {
+    let fallback_command = strategy
+        .fallback_command()
+        .ok_or(CompressionError::CompressionFailed)?;
+    let max_block_size = strategy.max_block_size();
+    let max_fallback_size = fallback_command.max_block_size();
+    let mut costs = vec![usize::MAX; source.len() + 1];
+    let mut choices: Vec<Option<Choice>> = vec![None; source.len() + 1];
+    costs[0] = 0;
+
+    let mut history_table = HistoryTable::new(source).set_search_depth(options.search_depth);
+    for i in 0..source.len() {
+        history_table.insert_until(i);
+        let cost = costs[i];
+
+        let max_size = cmp::min(max_fallback_size, source.len() - i);
+        for size in 1..=max_size {
+            let block_cost = fallback_command.header_size(size) + size;
+            let choice = Choice::new(i, size, None, size);
+            relax(&mut costs, &mut choices, choice, cost + block_cost);
+        }
+
+        let block_source = get_source_slice(source, i, max_block_size);
+        for (command_idx, command) in strategy.commands().iter().enumerate() {
+            let mut source_size = block_source.len();
+            let mut next_block = command.call(block_source, i, &history_table);
+            while let Some(block) = next_block {
+                let num_arguments = get_num_arguments(command, &block);
+                for size in 1..=block.num_bytes_consumed {
+                    let block_cost = command.header_size(size) + num_arguments;
+                    let choice = Choice::new(i, size, Some(command_idx), source_size);
+                    relax(&mut costs, &mut choices, choice, cost + block_cost);
+                }
+                next_block = find_cheaper_block(command, block_source, i, &history_table, &block)
+                    .map(|(size, block)| {
+                        source_size = size;
+                        block
+                    });
+            }
+        }
+    }
+
+    let mut path = Vec::new();
+    let mut end_index = source.len();
+    while end_index > 0 {
+        let choice = choices[end_index].ok_or(CompressionError::CompressionFailed)?;
+        path.push(choice);
+        end_index = choice.start_index;
+    }
+    path.reverse();
+    Ok(path)
+}
+
+/// Finds the longest block of `command` with fewer arguments than `block`, along with the length
+/// of the source it was built from.
+///
+/// Cutting the source short only ever makes a command prefer blocks with fewer arguments, so the
+/// longest source that still gives such a block is found with a binary search. The search starts
+/// at the shortest source where a block with fewer arguments would be worth writing.
+fn find_cheaper_block(
+    command: &Command,
+    source: &[u8],
+    index: usize,
+    history_table: &HistoryTable,
+    block: &Block,
+) -> Option<(usize, Block)> {
+    let num_arguments = get_num_arguments(command, block);
+    let find_block = |source_size: usize| {
+        command
+            .call(&source[..source_size], index, history_table)
+            .filter(|block| get_num_arguments(command, block) < num_arguments)
+    };
+
+    let mut low = num_arguments + 1;
+    let mut high = block.num_bytes_consumed;
+    if num_arguments == 0 || low >= high {
+        return None;
+    }
+    let mut cheaper_block = find_block(low)?;
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        match find_block(mid) {
+            Some(block) => {
+                low = mid;
+                cheaper_block = block;
+            }
+            None => high = mid,
+        }
+    }
+    Some((low, cheaper_block))
+}
+
+fn get_num_arguments(command: &Command, block: &Block) -> usize {
+    block.len() - command.header_size(block.num_bytes_consumed)
+}
+
+fn relax(costs: &mut [usize], choices: &mut [Option<Choice>], choice: Choice, cost: usize) {
+    let end_index = choice.end_index();
+    if cost < costs[end_index] {
+        costs[end_index] = cost;
+        choices[end_index] = Some(choice);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::greedy::compress_greedy;
+    use crate::compression::strategies::get_compression_strategy;
+    use crate::{decompress, CompressionType};
+
+    #[test]
+    fn compress_optimal_round_trips() {
+        let compression_types = [
+            CompressionType::LZ1,
+            CompressionType::LZ2,
+            CompressionType::LZ3,
+            CompressionType::LZ4,
+            CompressionType::LZ5,
+            CompressionType::LZ19,
+        ];
+        let mut source = b"APPLE_BANANA_APPLE_BANANA_ELPPA_000000_0123456_APPLEBANANA".to_vec();
+        source.extend(b"APPLE".iter().map(|&x| x ^ 0xFF));
+        source.extend(b"BANANA".iter().map(|&x| x.reverse_bits()));
+        for compression_type in compression_types {
+            let options = CompressionOptions::default();
+            let strategy = get_compression_strategy(compression_type, &options).unwrap();
+            let optimal = compress_optimal(&source, &strategy, &options).unwrap();
+            let greedy = compress_greedy(&source, &strategy, &options).unwrap();
+            let decompressed = decompress(&optimal, compression_type).unwrap();
+            assert_eq!(decompressed, source);
+            assert!(optimal.len() <= greedy.len());
+        }
+    }
+
+    #[test]
+    fn compress_optimal_beats_greedy() {
+        let source = b"TGTGTGTCCTTAGAGGGGTCTTTT";
+        let options = CompressionOptions::default();
+        let strategy = get_compression_strategy(CompressionType::LZ5, &options).unwrap();
+        let optimal = compress_optimal(source, &strategy, &options).unwrap();
+        let greedy = compress_greedy(source, &strategy, &options).unwrap();
+        assert_eq!(decompress(&optimal, CompressionType::LZ5).unwrap(), source);
+        assert!(optimal.len() < greedy.len());
+    }
+
+    #[test]
+    fn compress_optimal_handles_empty_source() {
+        let options = CompressionOptions::default();
+        let strategy = get_compression_strategy(CompressionType::LZ5, &options).unwrap();
+        let compressed = compress_optimal(&[], &strategy, &options).unwrap();
+        assert_eq!(compressed, vec![0xFF]);
+    }
+
+    /// Finds the size of the smallest stream by calling every command with every length of source
+    /// at every position, and cutting every block it returns to every size.
+    fn brute_force_size(source: &[u8], strategy: &CompressionStrategy) -> usize {
+        let fallback_command = strategy.fallback_command().unwrap();
+        let mut costs = vec![usize::MAX; source.len() + 1];
+        costs[0] = 0;
+        let mut history_table = HistoryTable::new(source);
+        for i in 0..source.len() {
+            if i > 0 {
+                history_table.insert(source[i - 1], source[i], i - 1);
+            }
+            let mut candidates = Vec::new();
+            let max_size = cmp::min(fallback_command.max_block_size(), source.len() - i);
+            for size in 1..=max_size {
+                candidates.push((size, fallback_command.header_size(size) + size));
+            }
+            let max_size = cmp::min(strategy.max_block_size(), source.len() - i);
+            for command in strategy.commands() {
+                for source_size in 1..=max_size {
+                    let block_source = &source[i..i + source_size];
+                    if let Some(block) = command.call(block_source, i, &history_table) {
+                        let num_arguments = get_num_arguments(command, &block);
+                        for size in 1..=block.num_bytes_consumed {
+                            candidates.push((size, command.header_size(size) + num_arguments));
+                        }
+                    }
+                }
+            }
+            for (size, block_cost) in candidates {
+                costs[i + size] = cmp::min(costs[i + size], costs[i] + block_cost);
+            }
+        }
+        costs[source.len()] + 1
+    }
+
+    #[test]
+    fn compress_optimal_matches_brute_force() {
+        let compression_types = [
+            CompressionType::LZ1,
+            CompressionType::LZ2,
+            CompressionType::LZ3,
+            CompressionType::LZ4,
+            CompressionType::LZ5,
+            CompressionType::LZ19,
+        ];
+        // Runs of a few symbols, with a copy of the start far enough away that LZ3 has to weigh
+        // its absolute offsets against shorter relative ones.
+        let mut seed: u32 = 5;
+        let mut next_byte = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as u8
+        };
+        let mut source: Vec<u8> = (0..60).map(|_| next_byte() % 4).collect();
+        source.extend((0..100).map(|_| next_byte()));
+        source.extend_from_within(..40);
+        source.extend((0..40).map(|_| next_byte() % 4));
+        for compression_type in compression_types {
+            let options = CompressionOptions::default();
+            let strategy = get_compression_strategy(compression_type, &options).unwrap();
+            let optimal = compress_optimal(&source, &strategy, &options).unwrap();
+            assert_eq!(decompress(&optimal, compression_type).unwrap(), source);
+            assert_eq!(optimal.len(), brute_force_size(&source, &strategy));
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::Framing;
+
+/// How hard the compressor works to make the output small.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CompressionLevel {
+    /// Takes the best block at each position without looking ahead.
+    #[default]
+    Fast,
+    /// Like `Fast`, but passes on a block when the block at the next position saves more.
+    Lazy,
+    /// Weighs every block at every position to produce the smallest output. This is much slower.
+    Optimal,
+}
+
+/// Settings that trade compression speed for output size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompressionOptions {
+    pub level: CompressionLevel,
+    /// The maximum number of earlier positions checked when searching for a repeat.
+    pub search_depth: usize,
+    pub use_xor_commands: bool,
+    pub use_increasing_fill: bool,
+    /// Decompresses the output and checks it against the source before returning it.
+    pub verify: bool,
+    pub framing: Framing,
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        CompressionOptions {
+            level: CompressionLevel::default(),
+            search_depth: usize::MAX,
+            use_xor_commands: true,
+            use_increasing_fill: true,
+            verify: false,
+            framing: Framing::default(),
+        }
+    }
+}
+
+impl CompressionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_level(mut self, level: CompressionLevel) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn set_search_depth(mut self, search_depth: usize) -> Self {
+        self.search_depth = search_depth;
+        self
+    }
+
+    pub fn set_xor_commands(mut self, use_xor_commands: bool) -> Self {
+        self.use_xor_commands = use_xor_commands;
+        self
+    }
+
+    pub fn set_increasing_fill(mut self, use_increasing_fill: bool) -> Self {
+        self.use_increasing_fill = use_increasing_fill;
+        self
+    }
+
+    pub fn set_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    pub fn set_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+}
@@ -0,0 +1,91 @@
+use alloc::vec::Vec;
+
+const MAX_COPY_SIZE: usize = 0x80;
+const MAX_FILL_SIZE: usize = 0x7F;
+const MIN_FILL_SIZE: usize = 3;
+
+/// Compresses `source` into a single RLE1 stream, including the `0xFF` terminator.
+///
+/// Fills are capped at 127 bytes so that a fill header can never be mistaken for the terminator.
+pub fn compress_rle1(source: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    let mut copy_start_index = 0;
+    let mut i = 0;
+    while i < source.len() {
+        let fill_size = get_fill_size(&source[i..]);
+        if fill_size >= MIN_FILL_SIZE {
+            push_direct_copies(&mut buffer, &source[copy_start_index..i]);
+            buffer.push(0x80 | (fill_size - 1) as u8);
+            buffer.push(source[i]);
+            i += fill_size;
+            copy_start_index = i;
+        } else {
+            i += 1;
+        }
+    }
+    push_direct_copies(&mut buffer, &source[copy_start_index..]);
+    buffer.push(0xFF);
+    buffer
+}
+
+/// Compresses `source` into an RLE2 stream: the even bytes as one RLE1 stream followed by the
+/// odd bytes as another, since neighbouring bytes of a 2bpp tile belong to different bitplanes.
+pub fn compress_rle2(source: &[u8]) -> Vec<u8> {
+    let even_plane: Vec<u8> = source.iter().step_by(2).copied().collect();
+    let odd_plane: Vec<u8> = source.iter().skip(1).step_by(2).copied().collect();
+    let mut buffer = compress_rle1(&even_plane);
+    buffer.append(&mut compress_rle1(&odd_plane));
+    buffer
+}
+
+fn get_fill_size(source: &[u8]) -> usize {
+    let first_byte = source[0];
+    source
+        .iter()
+        .take(MAX_FILL_SIZE)
+        .take_while(|&&byte| byte == first_byte)
+        .count()
+}
+
+fn push_direct_copies(buffer: &mut Vec<u8>, source: &[u8]) {
+    for chunk in source.chunks(MAX_COPY_SIZE) {
+        buffer.push((chunk.len() - 1) as u8);
+        buffer.extend_from_slice(chunk);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rle1_fill_works() {
+        let source = &[0xAA, 0xAA, 0xAA, 0xAA];
+        assert_eq!(compress_rle1(source), vec![0x83, 0xAA, 0xFF]);
+    }
+
+    #[test]
+    fn rle1_direct_copy_works() {
+        let source = &[0xAA, 0xAB, 0xAB, 0xAC];
+        let expected = vec![0x03, 0xAA, 0xAB, 0xAB, 0xAC, 0xFF];
+        assert_eq!(compress_rle1(source), expected);
+    }
+
+    #[test]
+    fn rle1_splits_long_fills() {
+        let source = &[0xAA; 200];
+        assert_eq!(compress_rle1(source), vec![0xFE, 0xAA, 0xC8, 0xAA, 0xFF]);
+    }
+
+    #[test]
+    fn rle1_empty_source_is_only_terminator() {
+        assert_eq!(compress_rle1(&[]), vec![0xFF]);
+    }
+
+    #[test]
+    fn rle2_splits_planes() {
+        let source = &[0xAA, 0x01, 0xAA, 0x02, 0xAA, 0x03];
+        let expected = vec![0x82, 0xAA, 0xFF, 0x02, 0x01, 0x02, 0x03, 0xFF];
+        assert_eq!(compress_rle2(source), expected);
+    }
+}
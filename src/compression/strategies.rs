@@ -1,17 +1,26 @@
-use std::cmp;
+use alloc::vec::Vec;
+use core::cmp;
 
 use super::block::Block;
 use super::command::{Command, CommandConfiguration};
 use super::command_callbacks as commands;
 use super::history_table::HistoryTable;
+use super::options::CompressionOptions;
 use super::CompressionType;
 use commands::CommandCallback;
 
+/// The commands a format can be compressed with.
+///
+/// Formats that are not built in can be described by inserting the callbacks in
+/// [`compression_commands`](crate::compression_commands), and compressed with
+/// [`compress_with_strategy`](crate::compress_with_strategy).
+#[derive(Clone)]
 pub struct CompressionStrategy {
     commands: Vec<Command>,
     fallback_command: Option<Command>,
     cmd_size: usize,
     max_block_size: usize,
+    terminator: u8,
 }
 
 impl CompressionStrategy {
@@ -44,82 +53,239 @@ impl CompressionStrategy {
         end_index: usize,
         history_table: &HistoryTable,
     ) -> Option<Vec<Block>> {
-        if self.fallback_command.is_none() {
-            return None;
-        }
-        let max_size = end_index - start_index;
+        let fallback_command = self.fallback_command.as_ref()?;
         let mut blocks = Vec::new();
-        let num_iterations = (max_size as f32 / self.max_block_size as f32).ceil() as usize;
-        for i in 0..num_iterations {
-            let source = get_source_slice(source, start_index, max_size);
-            let fallback_command = self.fallback_command.as_ref().unwrap();
-            blocks.push(fallback_command.call(source, start_index, history_table)?);
+        let mut block_start_index = start_index;
+        while block_start_index < end_index {
+            let max_size = cmp::min(end_index - block_start_index, self.max_block_size);
+            let source = get_source_slice(source, block_start_index, max_size);
+            let block = fallback_command.call(source, block_start_index, history_table)?;
+            block_start_index += block.num_bytes_consumed;
+            blocks.push(block);
         }
         Some(blocks)
     }
 
-    fn new(cmd_size: usize, max_block_size: usize) -> Self {
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    pub fn fallback_command(&self) -> Option<&Command> {
+        self.fallback_command.as_ref()
+    }
+
+    pub fn max_block_size(&self) -> usize {
+        self.max_block_size
+    }
+
+    pub fn terminator(&self) -> u8 {
+        self.terminator
+    }
+
+    /// Creates a strategy without any commands and with a `0xFF` terminator. `cmd_size` is the
+    /// number of bits of the command number in a header, and `max_block_size` is the most bytes a
+    /// single command can write. Both are clamped to what a header can hold, see
+    /// [`CommandConfiguration::clamp_sizes`].
+    pub fn new(cmd_size: usize, max_block_size: usize) -> Self {
+        let (cmd_size, max_block_size) =
+            CommandConfiguration::clamp_sizes(cmd_size, max_block_size);
         CompressionStrategy {
             commands: Vec::new(),
             fallback_command: None,
             cmd_size,
             max_block_size,
+            terminator: 0xFF,
         }
     }
 
-    fn insert_command(
+    /// Sets the byte written at the end of the stream. Commands are kept short enough that their
+    /// headers never start with it.
+    pub fn set_terminator(mut self, terminator: u8) -> Self {
+        self.terminator = terminator;
+        let commands = core::mem::take(&mut self.commands);
+        self.commands = commands
+            .iter()
+            .map(|command| {
+                self.build_command(
+                    command.config().cmd_num,
+                    command.config().is_extended_only,
+                    command.callback(),
+                )
+            })
+            .collect();
+        self.fallback_command = self.fallback_command.take().map(|command| {
+            self.build_command(
+                command.config().cmd_num,
+                command.config().is_extended_only,
+                command.callback(),
+            )
+        });
+        self
+    }
+
+    /// Adds `callback` as command `cmd_num`. If `is_extended_only` is set, the command is always
+    /// written with the long form header.
+    pub fn insert_command(
         mut self,
         cmd_num: u8,
         is_extended_only: bool,
         callback: CommandCallback,
     ) -> Self {
-        let cmd_size = self.cmd_size;
-        let max_block_size = self.max_block_size;
-        let command_config =
-            CommandConfiguration::new(cmd_num, cmd_size, max_block_size, is_extended_only);
-        let cmd = Command::new(command_config, callback);
+        let cmd = self.build_command(cmd_num, is_extended_only, callback);
         self.commands.push(cmd);
         self
     }
 
-    fn set_fallback_command(
+    /// Adds `callback` like [`insert_command`](CompressionStrategy::insert_command), but only if
+    /// `condition` holds.
+    pub fn insert_command_if(
+        self,
+        condition: bool,
+        cmd_num: u8,
+        is_extended_only: bool,
+        callback: CommandCallback,
+    ) -> Self {
+        if condition {
+            self.insert_command(cmd_num, is_extended_only, callback)
+        } else {
+            self
+        }
+    }
+
+    /// Sets the command used for data that no other command can write, which is usually a direct
+    /// copy.
+    pub fn set_fallback_command(
         mut self,
         cmd_num: u8,
         is_extended_only: bool,
         callback: CommandCallback,
     ) -> Self {
+        let cmd = self.build_command(cmd_num, is_extended_only, callback);
+        self.fallback_command = Some(cmd);
+        self
+    }
+
+    fn build_command(
+        &self,
+        cmd_num: u8,
+        is_extended_only: bool,
+        callback: CommandCallback,
+    ) -> Command {
         let cmd_size = self.cmd_size;
         let max_block_size = self.max_block_size;
         let command_config =
-            CommandConfiguration::new(cmd_num, cmd_size, max_block_size, is_extended_only);
-        let cmd = Command::new(command_config, callback);
-        self.fallback_command = Some(cmd);
-        self
+            CommandConfiguration::new(cmd_num, cmd_size, max_block_size, is_extended_only)
+                .avoid_terminator(self.terminator);
+        Command::new(command_config, callback)
     }
 }
 
-pub fn get_compression_strategy(compression_type: CompressionType) -> Option<CompressionStrategy> {
+pub fn get_compression_strategy(
+    compression_type: CompressionType,
+    options: &CompressionOptions,
+) -> Option<CompressionStrategy> {
     match compression_type {
-        CompressionType::LZ5 => Some(lz5_compression_strategy()),
+        CompressionType::LZ1 => Some(lz1_compression_strategy(options)),
+        CompressionType::LZ2 => Some(lz2_compression_strategy(options)),
+        CompressionType::LZ3 => Some(lz3_compression_strategy()),
+        CompressionType::LZ4 => Some(lz4_compression_strategy(options)),
+        CompressionType::LZ5 => Some(lz5_compression_strategy(options)),
+        CompressionType::LZ19 => Some(lz19_compression_strategy(options)),
+        CompressionType::RLE1 | CompressionType::RLE2 => None,
     }
 }
 
+// https://github.com/bonimy/MushROMs/blob/master/doc/LC_LZ1%20Compression%20Format.md
+fn lz1_compression_strategy(options: &CompressionOptions) -> CompressionStrategy {
+    let cmd_size = 3;
+    let max_block_size = 1024;
+    let use_increasing_fill = options.use_increasing_fill;
+    CompressionStrategy::new(cmd_size, max_block_size)
+        .set_fallback_command(0b000, false, commands::direct_copy)
+        .insert_command(0b001, false, commands::byte_fill)
+        .insert_command(0b010, false, commands::word_fill)
+        .insert_command_if(use_increasing_fill, 0b011, false, commands::increasing_fill)
+        .insert_command(0b100, false, commands::repeat_be)
+}
+
+// https://github.com/bonimy/MushROMs/blob/master/doc/LC_LZ2%20Compression%20Format.md
+fn lz2_compression_strategy(options: &CompressionOptions) -> CompressionStrategy {
+    let cmd_size = 3;
+    let max_block_size = 1024;
+    let use_increasing_fill = options.use_increasing_fill;
+    CompressionStrategy::new(cmd_size, max_block_size)
+        .set_fallback_command(0b000, false, commands::direct_copy)
+        .insert_command(0b001, false, commands::byte_fill)
+        .insert_command(0b010, false, commands::word_fill)
+        .insert_command_if(use_increasing_fill, 0b011, false, commands::increasing_fill)
+        .insert_command(0b100, false, commands::repeat_be)
+}
+
+// https://github.com/pret/pokecrystal/blob/master/tools/lz/lz.h
+fn lz3_compression_strategy() -> CompressionStrategy {
+    let cmd_size = 3;
+    let max_block_size = 1024;
+    CompressionStrategy::new(cmd_size, max_block_size)
+        .set_fallback_command(0b000, false, commands::direct_copy)
+        .insert_command(0b001, false, commands::byte_fill)
+        .insert_command(0b010, false, commands::word_fill)
+        .insert_command(0b011, false, commands::zero_fill)
+        .insert_command(0b100, false, commands::signed_repeat)
+        .insert_command(0b101, false, commands::signed_bit_reversed_repeat)
+        .insert_command(0b110, false, commands::signed_backwards_repeat)
+}
+
+// https://github.com/bonimy/MushROMs/blob/master/doc/LC_LZ4%20Compression%20Format.md
+fn lz4_compression_strategy(options: &CompressionOptions) -> CompressionStrategy {
+    let cmd_size = 3;
+    let max_block_size = 1024;
+    let use_increasing_fill = options.use_increasing_fill;
+    let use_xor_commands = options.use_xor_commands;
+    CompressionStrategy::new(cmd_size, max_block_size)
+        .set_fallback_command(0b000, false, commands::direct_copy)
+        .insert_command(0b001, false, commands::byte_fill)
+        .insert_command(0b010, false, commands::word_fill)
+        .insert_command_if(use_increasing_fill, 0b011, false, commands::increasing_fill)
+        .insert_command(0b100, false, commands::repeat_be)
+        .insert_command_if(use_xor_commands, 0b101, false, commands::xor_repeat_be)
+        .insert_command(0b110, false, commands::negative_repeat)
+        .insert_command_if(use_xor_commands, 0b111, true, commands::negative_xor_repeat)
+}
+
 // https://github.com/bonimy/MushROMs/blob/master/doc/LC_LZ5%20Compression%20Format.md
-fn lz5_compression_strategy() -> CompressionStrategy {
+fn lz5_compression_strategy(options: &CompressionOptions) -> CompressionStrategy {
+    let cmd_size = 3;
+    let max_block_size = 1024;
+    let use_increasing_fill = options.use_increasing_fill;
+    let use_xor_commands = options.use_xor_commands;
+    CompressionStrategy::new(cmd_size, max_block_size)
+        .set_fallback_command(0b000, false, commands::direct_copy)
+        .insert_command(0b001, false, commands::byte_fill)
+        .insert_command(0b010, false, commands::word_fill)
+        .insert_command_if(use_increasing_fill, 0b011, false, commands::increasing_fill)
+        .insert_command(0b100, false, commands::repeat_le)
+        .insert_command_if(use_xor_commands, 0b101, false, commands::xor_repeat_le)
+        .insert_command(0b110, false, commands::negative_repeat)
+        .insert_command_if(use_xor_commands, 0b111, true, commands::negative_xor_repeat)
+}
+
+// Same as LZ5, except that 0b011 fills with zeroes instead of an increasing sequence.
+fn lz19_compression_strategy(options: &CompressionOptions) -> CompressionStrategy {
     let cmd_size = 3;
     let max_block_size = 1024;
+    let use_xor_commands = options.use_xor_commands;
     CompressionStrategy::new(cmd_size, max_block_size)
         .set_fallback_command(0b000, false, commands::direct_copy)
         .insert_command(0b001, false, commands::byte_fill)
         .insert_command(0b010, false, commands::word_fill)
-        .insert_command(0b011, false, commands::increasing_fill)
+        .insert_command(0b011, false, commands::zero_fill)
         .insert_command(0b100, false, commands::repeat_le)
-        .insert_command(0b101, false, commands::xor_repeat_le)
+        .insert_command_if(use_xor_commands, 0b101, false, commands::xor_repeat_le)
         .insert_command(0b110, false, commands::negative_repeat)
-        .insert_command(0b111, true, commands::negative_xor_repeat)
+        .insert_command_if(use_xor_commands, 0b111, true, commands::negative_xor_repeat)
 }
 
-fn get_source_slice(source: &[u8], start_index: usize, max_size: usize) -> &[u8] {
+pub fn get_source_slice(source: &[u8], start_index: usize, max_size: usize) -> &[u8] {
     let upper_bound = cmp::min(start_index + max_size, source.len());
     &source[start_index..upper_bound]
 }
@@ -1,147 +1,236 @@
-use super::DecompResult;
+//! The commands that LZ formats are made of, for building a
+//! [`DecompressionStrategy`](crate::DecompressionStrategy).
+//!
+//! Each callback reads its arguments from the start of `source`, writes `cmd_size` bytes to the
+//! buffer, and returns the number of argument bytes it read.
+
+use super::{output_buffer::OutputBuffer, DecompResult};
 use crate::errors::DecompressionErrorKind;
 
-pub type CommandCallback = fn(&[u8], &mut Vec<u8>, usize) -> DecompResult<usize>;
+pub type CommandCallback = fn(&[u8], &mut dyn OutputBuffer, usize) -> DecompResult<usize>;
 
 pub fn direct_copy(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     cmd_size: usize,
 ) -> DecompResult<usize> {
-    for &byte in source.iter().take(cmd_size) {
-        buffer.push(byte);
-    }
+    let bytes = source
+        .get(..cmd_size)
+        .ok_or(DecompressionErrorKind::IndexOutOfBounds)?;
+    buffer.extend_from_slice(bytes)?;
     Ok(cmd_size)
 }
 
 pub fn byte_fill(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     cmd_size: usize,
 ) -> DecompResult<usize> {
     let byte = try_get_byte(source, 0)?;
     for _ in 0..cmd_size {
-        buffer.push(byte);
+        buffer.push(byte)?;
     }
     Ok(1)
 }
 
 pub fn word_fill(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     cmd_size: usize,
 ) -> DecompResult<usize> {
     let byte1: u8 = try_get_byte(source, 0)?;
     let byte2: u8 = try_get_byte(source, 1)?;
     for i in 0..cmd_size {
         let to_push = if i % 2 == 0 { byte1 } else { byte2 };
-        buffer.push(to_push);
+        buffer.push(to_push)?;
     }
     Ok(2)
 }
 
+/// Writes a byte that goes up by one each time. It wraps from `0xFF` to `0x00`, like the 8 bit
+/// increment in the SNES decompression routines, which Lunar Compress matches. A fill that wraps
+/// is valid data, not an error.
 pub fn increasing_fill(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     cmd_size: usize,
 ) -> DecompResult<usize> {
-    let mut byte: u8 = try_get_byte(source, 0)?;
-    for _ in 0..cmd_size {
-        buffer.push(byte);
-        byte += 1
+    let byte: u8 = try_get_byte(source, 0)?;
+    for i in 0..cmd_size {
+        buffer.push(byte.wrapping_add(i as u8))?;
     }
     Ok(1)
 }
 
+pub fn zero_fill(
+    _source: &[u8],
+    buffer: &mut dyn OutputBuffer,
+    cmd_size: usize,
+) -> DecompResult<usize> {
+    for _ in 0..cmd_size {
+        buffer.push(0)?;
+    }
+    Ok(0)
+}
+
 pub fn repeat_be(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     cmd_size: usize,
 ) -> DecompResult<usize> {
     let byte1: usize = try_get_byte(source, 0)?;
     let byte2: usize = try_get_byte(source, 1)?;
-    let mut offset = (byte1 << 8) | byte2;
-    for _ in 0..cmd_size {
-        buffer.push(buffer[offset]);
-        offset += 1;
+    let offset = (byte1 << 8) | byte2;
+    for i in offset..offset + cmd_size {
+        let byte = try_get_previous_byte(buffer, i)?;
+        buffer.push(byte)?;
     }
     Ok(2)
 }
 
 pub fn repeat_le(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     cmd_size: usize,
 ) -> DecompResult<usize> {
     let byte1: usize = try_get_byte(source, 1)?;
     let byte2: usize = try_get_byte(source, 0)?;
-    let mut offset = (byte1 << 8) | byte2;
-    for _ in 0..cmd_size {
-        buffer.push(buffer[offset]);
-        offset += 1;
+    let offset = (byte1 << 8) | byte2;
+    for i in offset..offset + cmd_size {
+        let byte = try_get_previous_byte(buffer, i)?;
+        buffer.push(byte)?;
     }
     Ok(2)
 }
 
 pub fn xor_repeat_be(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     cmd_size: usize,
 ) -> DecompResult<usize> {
     let byte1: usize = try_get_byte(source, 0)?;
     let byte2: usize = try_get_byte(source, 1)?;
-    let mut offset = (byte1 << 8) | byte2;
-    for _ in 0..cmd_size {
-        buffer.push(buffer[offset] ^ 0xFF);
-        offset += 1;
+    let offset = (byte1 << 8) | byte2;
+    for i in offset..offset + cmd_size {
+        let byte = try_get_previous_byte(buffer, i)?;
+        buffer.push(byte ^ 0xFF)?;
     }
     Ok(2)
 }
 
 pub fn xor_repeat_le(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     cmd_size: usize,
 ) -> DecompResult<usize> {
     let byte1: usize = try_get_byte(source, 1)?;
     let byte2: usize = try_get_byte(source, 0)?;
-    let mut offset = (byte1 << 8) | byte2;
-    for _ in 0..cmd_size {
-        buffer.push(buffer[offset] ^ 0xFF);
-        offset += 1;
+    let offset = (byte1 << 8) | byte2;
+    for i in offset..offset + cmd_size {
+        let byte = try_get_previous_byte(buffer, i)?;
+        buffer.push(byte ^ 0xFF)?;
     }
     Ok(2)
 }
 
 pub fn negative_repeat(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     cmd_size: usize,
 ) -> DecompResult<usize> {
     let byte: usize = try_get_byte(source, 0)?;
-    let mut offset = buffer.len() - byte;
-    for _ in 0..cmd_size {
-        buffer.push(buffer[offset]);
-        offset += 1;
+    let offset = try_get_negative_offset(buffer, byte)?;
+    for i in offset..offset + cmd_size {
+        let byte = try_get_previous_byte(buffer, i)?;
+        buffer.push(byte)?;
     }
     Ok(1)
 }
 
 pub fn negative_xor_repeat_le(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     cmd_size: usize,
 ) -> DecompResult<usize> {
     let byte: usize = try_get_byte(source, 0)?;
-    let mut offset = buffer.len() - byte;
-    for _ in 0..cmd_size {
-        buffer.push(buffer[offset] ^ 0xFF);
-        offset += 1;
+    let offset = try_get_negative_offset(buffer, byte)?;
+    for i in offset..offset + cmd_size {
+        let byte = try_get_previous_byte(buffer, i)?;
+        buffer.push(byte ^ 0xFF)?;
     }
     Ok(1)
 }
 
-fn try_get_byte<T: std::convert::From<u8>>(source: &[u8], idx: usize) -> DecompResult<T> {
+pub fn signed_repeat(
+    source: &[u8],
+    buffer: &mut dyn OutputBuffer,
+    cmd_size: usize,
+) -> DecompResult<usize> {
+    let (offset, num_skip) = try_get_signed_offset(source, buffer)?;
+    for i in offset..offset + cmd_size {
+        let byte = try_get_previous_byte(buffer, i)?;
+        buffer.push(byte)?;
+    }
+    Ok(num_skip)
+}
+
+pub fn signed_bit_reversed_repeat(
+    source: &[u8],
+    buffer: &mut dyn OutputBuffer,
+    cmd_size: usize,
+) -> DecompResult<usize> {
+    let (offset, num_skip) = try_get_signed_offset(source, buffer)?;
+    for i in offset..offset + cmd_size {
+        let byte = try_get_previous_byte(buffer, i)?;
+        buffer.push(byte.reverse_bits())?;
+    }
+    Ok(num_skip)
+}
+
+pub fn signed_backwards_repeat(
+    source: &[u8],
+    buffer: &mut dyn OutputBuffer,
+    cmd_size: usize,
+) -> DecompResult<usize> {
+    let (offset, num_skip) = try_get_signed_offset(source, buffer)?;
+    for i in 0..cmd_size {
+        let idx = offset
+            .checked_sub(i)
+            .ok_or(DecompressionErrorKind::InvalidOffset)?;
+        let byte = try_get_previous_byte(buffer, idx)?;
+        buffer.push(byte)?;
+    }
+    Ok(num_skip)
+}
+
+/// Reads an LZ3 style offset. A set high bit marks a one byte offset counting back from the
+/// end of the buffer, otherwise the offset is a two byte big endian absolute index.
+fn try_get_signed_offset(source: &[u8], buffer: &dyn OutputBuffer) -> DecompResult<(usize, usize)> {
+    let byte1: usize = try_get_byte(source, 0)?;
+    if byte1 & 0x80 != 0 {
+        let offset = try_get_negative_offset(buffer, (byte1 & 0x7F) + 1)?;
+        Ok((offset, 1))
+    } else {
+        let byte2: usize = try_get_byte(source, 1)?;
+        let offset = (byte1 << 8) | byte2;
+        Ok((offset, 2))
+    }
+}
+
+fn try_get_negative_offset(buffer: &dyn OutputBuffer, distance: usize) -> DecompResult<usize> {
+    buffer
+        .len()
+        .checked_sub(distance)
+        .ok_or(DecompressionErrorKind::InvalidOffset)
+}
+
+fn try_get_previous_byte(buffer: &dyn OutputBuffer, idx: usize) -> DecompResult<u8> {
+    buffer
+        .byte_at(idx)
+        .ok_or(DecompressionErrorKind::InvalidOffset)
+}
+
+fn try_get_byte<T: From<u8>>(source: &[u8], idx: usize) -> DecompResult<T> {
     if idx < source.len() {
         Ok(source[idx].into())
     } else {
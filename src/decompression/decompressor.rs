@@ -0,0 +1,429 @@
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+use super::{
+    check_output_size, decompress_info_with_options, get_decompression_strategy,
+    is_output_complete, output_buffer::OutputBuffer, process_next, stragies::DecompressionStrategy,
+    DecompResult, DecompressionOptions,
+};
+use crate::errors::{DecompressionError, DecompressionErrorKind};
+use crate::framing::{self, Framing};
+use crate::CompressionType;
+
+/// The most bytes a single command writes.
+const MAX_BLOCK_SIZE: usize = 0x400;
+
+/// The longest a single command can be: a long form header followed by a 1024 byte direct copy.
+const MAX_COMMAND_LENGTH: usize = 2 + MAX_BLOCK_SIZE;
+
+/// Absolute offsets are 16 bits and a repeat copies up to 1024 bytes, so no absolute reference
+/// can reach past this many bytes of output.
+const MAX_ABSOLUTE_REFERENCE: usize = 0x10000 + MAX_BLOCK_SIZE;
+
+/// Relative offsets count back at most 255 bytes from the end of the output.
+const MAX_RELATIVE_DISTANCE: usize = 0x100;
+
+/// LZ3's backwards repeat starts up to 128 bytes back and copies towards the start of the output
+/// from there, so it can reach a whole block further.
+const MAX_BACKWARDS_DISTANCE: usize = 0x80 + MAX_BLOCK_SIZE;
+
+/// Decompresses a stream as it is read.
+///
+/// Compressed bytes are pulled from `source` one command at a time, and the output is handed out
+/// through [`Read`]. Only the history that later commands can refer to is kept: the first
+/// 65 KiB of output for absolute offsets, and the last 256 bytes for relative ones, or 1152 bytes
+/// for LZ3, whose backwards repeats copy a whole block back from their relative offset.
+///
+/// The RLE formats are not made of commands, and RLE2 stores its two bitplanes one after the
+/// other, so they are read in full on the first call to `read`.
+///
+/// Up to one command's worth of bytes past the end of the stream may be read from `source`. Only
+/// the framing is taken from the options given to [`with_options`](Decompressor::with_options).
+/// Errors are
+/// returned as [`io::ErrorKind::InvalidData`] wrapping a [`DecompressionError`]. The compressed
+/// data is not kept, so the error never holds copies of the buffers.
+pub struct Decompressor<R: Read> {
+    source: R,
+    compression_type: CompressionType,
+    strategy: Option<DecompressionStrategy>,
+    framing: Framing,
+    output_size: Option<usize>,
+    input: Vec<u8>,
+    num_bytes_consumed: usize,
+    output: HistoryWindow,
+    is_source_exhausted: bool,
+    is_finished: bool,
+}
+
+impl<R: Read> Decompressor<R> {
+    pub fn new(source: R, compression_type: CompressionType) -> Self {
+        Self::with_options(source, compression_type, DecompressionOptions::default())
+    }
+
+    pub fn with_options(
+        source: R,
+        compression_type: CompressionType,
+        options: DecompressionOptions,
+    ) -> Self {
+        let output_size = match options.framing {
+            Framing::OutputSize(output_size) => Some(output_size),
+            _ => None,
+        };
+        Decompressor {
+            source,
+            compression_type,
+            strategy: get_decompression_strategy(compression_type).ok(),
+            framing: options.framing,
+            output_size,
+            input: Vec::new(),
+            num_bytes_consumed: 0,
+            output: HistoryWindow::new(recent_window_size(compression_type)),
+            is_source_exhausted: false,
+            is_finished: false,
+        }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+
+    fn decompress_next(&mut self) -> io::Result<()> {
+        let is_size_prefix_unread =
+            self.framing == Framing::SizePrefix && self.output_size.is_none();
+        if self.strategy.is_some() && is_size_prefix_unread {
+            self.read_size_prefix()?;
+        }
+        let Some(strategy) = &self.strategy else {
+            return self.decompress_all();
+        };
+        if !self.is_source_exhausted {
+            self.is_source_exhausted = fill_input(&mut self.source, &mut self.input)?;
+        }
+        if self.input.is_empty() || is_output_complete(&self.output, self.output_size) {
+            self.is_finished = true;
+            return Ok(());
+        }
+        let terminator = match self.output_size {
+            Some(_) => None,
+            None => Some(strategy.terminator()),
+        };
+        let command_outcome = process_next(&self.input, &mut self.output, strategy, terminator)
+            .and_then(|command_outcome| {
+                check_output_size(&self.output, self.output_size)?;
+                Ok(command_outcome)
+            })
+            .map_err(|kind| self.build_error(kind, self.input.first().copied()))?;
+        self.input.drain(..command_outcome.num_bytes_consumed);
+        self.num_bytes_consumed += command_outcome.num_bytes_consumed;
+        self.is_finished = command_outcome.is_terminated;
+        Ok(())
+    }
+
+    fn read_size_prefix(&mut self) -> io::Result<()> {
+        self.is_source_exhausted = fill_input(&mut self.source, &mut self.input)?;
+        let output_size = framing::read_size_prefix(&self.input)
+            .ok_or_else(|| self.build_error(DecompressionErrorKind::IndexOutOfBounds, None))?;
+        self.input.drain(..framing::SIZE_PREFIX_LEN);
+        self.num_bytes_consumed += framing::SIZE_PREFIX_LEN;
+        self.output_size = Some(output_size);
+        Ok(())
+    }
+
+    fn decompress_all(&mut self) -> io::Result<()> {
+        self.source.read_to_end(&mut self.input)?;
+        let options = DecompressionOptions::new().set_framing(self.framing);
+        let decompression_info =
+            decompress_info_with_options(&self.input, self.compression_type, &options)
+                .map_err(into_io_error)?;
+        self.num_bytes_consumed = decompression_info.num_bytes_consumed;
+        self.output
+            .extend_from_slice(&decompression_info.data)
+            .map_err(|kind| self.build_error(kind, None))?;
+        self.is_finished = true;
+        Ok(())
+    }
+
+    fn build_error(&self, kind: DecompressionErrorKind, command_byte: Option<u8>) -> io::Error {
+        let error = DecompressionError::new(
+            kind,
+            self.num_bytes_consumed,
+            command_byte,
+            self.output.len(),
+        );
+        into_io_error(error)
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.output.num_unread() == 0 && !self.is_finished {
+            self.decompress_next()?;
+        }
+        Ok(self.output.read(buf))
+    }
+}
+
+/// How many recent bytes of output the relative offsets of `compression_type` can reach.
+fn recent_window_size(compression_type: CompressionType) -> usize {
+    match compression_type {
+        CompressionType::LZ3 => MAX_BACKWARDS_DISTANCE,
+        _ => MAX_RELATIVE_DISTANCE,
+    }
+}
+
+/// Reads from `source` until `input` holds a whole command. Returns whether `source` ran out.
+fn fill_input<R: Read>(source: &mut R, input: &mut Vec<u8>) -> io::Result<bool> {
+    let num_missing = MAX_COMMAND_LENGTH.saturating_sub(input.len());
+    let num_read = source.take(num_missing as u64).read_to_end(input)?;
+    Ok(num_read < num_missing)
+}
+
+fn into_io_error(error: DecompressionError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// The output of a [`Decompressor`], trimmed down to the bytes that can still be referenced or
+/// have not been read yet.
+struct HistoryWindow {
+    prefix: Vec<u8>,
+    recent: VecDeque<u8>,
+    recent_size: usize,
+    recent_start: usize,
+    len: usize,
+    num_read: usize,
+}
+
+impl HistoryWindow {
+    /// Creates a window that keeps at least the last `recent_size` bytes.
+    fn new(recent_size: usize) -> Self {
+        HistoryWindow {
+            prefix: Vec::new(),
+            recent: VecDeque::new(),
+            recent_size,
+            recent_start: MAX_ABSOLUTE_REFERENCE,
+            len: 0,
+            num_read: 0,
+        }
+    }
+
+    fn num_unread(&self) -> usize {
+        self.len - self.num_read
+    }
+
+    /// Copies unread bytes into `buf`, then drops the recent bytes that are no longer needed.
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let num_bytes = self.num_unread().min(buf.len());
+        for (i, byte) in buf[..num_bytes].iter_mut().enumerate() {
+            *byte = self.byte_at(self.num_read + i).unwrap_or_default();
+        }
+        self.num_read += num_bytes;
+        while self.recent.len() > self.recent_size && self.recent_start < self.num_read {
+            self.recent.pop_front();
+            self.recent_start += 1;
+        }
+        num_bytes
+    }
+}
+
+impl OutputBuffer for HistoryWindow {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn byte_at(&self, index: usize) -> Option<u8> {
+        if index < self.prefix.len() {
+            return Some(self.prefix[index]);
+        }
+        let recent_index = index.checked_sub(self.recent_start)?;
+        self.recent.get(recent_index).copied()
+    }
+
+    fn push(&mut self, byte: u8) -> DecompResult<()> {
+        if self.len < MAX_ABSOLUTE_REFERENCE {
+            self.prefix.push(byte);
+        } else {
+            self.recent.push_back(byte);
+        }
+        self.len += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, decompress};
+
+    /// Hands out at most `chunk_size` bytes per call, like a pipe.
+    struct ChunkedReader<'a> {
+        source: &'a [u8],
+        chunk_size: usize,
+    }
+
+    impl Read for ChunkedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let num_bytes = self.chunk_size.min(buf.len()).min(self.source.len());
+            buf[..num_bytes].copy_from_slice(&self.source[..num_bytes]);
+            self.source = &self.source[num_bytes..];
+            Ok(num_bytes)
+        }
+    }
+
+    fn get_test_data(size: usize) -> Vec<u8> {
+        let mut state: u32 = 0x12345678;
+        let mut data = Vec::new();
+        while data.len() < size {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            let byte = (state >> 24) as u8;
+            let run_size = (state >> 8) as usize % 12 + 1;
+            data.extend((0..run_size).map(|i| byte.wrapping_add((i as u8) & byte & 3)));
+        }
+        data.truncate(size);
+        data
+    }
+
+    #[test]
+    fn decompressor_matches_decompress() {
+        let compression_types = [
+            CompressionType::LZ1,
+            CompressionType::LZ2,
+            CompressionType::LZ3,
+            CompressionType::LZ4,
+            CompressionType::LZ5,
+            CompressionType::LZ19,
+            CompressionType::RLE1,
+            CompressionType::RLE2,
+        ];
+        let source = get_test_data(0x5000);
+        for compression_type in compression_types {
+            let compressed = compress(&source, compression_type).unwrap();
+            let reader = ChunkedReader {
+                source: &compressed,
+                chunk_size: 7,
+            };
+            let mut decompressed = Vec::new();
+            Decompressor::new(reader, compression_type)
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, source, "{compression_type}");
+        }
+    }
+
+    #[test]
+    fn decompressor_keeps_history_past_window() {
+        let mut source = vec![0b00000011, 1, 2, 3, 4];
+        for _ in 0..100 {
+            source.extend([0b11111011, 0xFF, 0x04]);
+        }
+        source.extend([0b10011111, 0x10, 0x00]);
+        source.extend([0b11110011, 0xFF, 0xF0, 0xFF]);
+        source.extend([0b11011111, 0x80]);
+        source.push(0xFF);
+        let mut decompressor = Decompressor::new(source.as_slice(), CompressionType::LZ5);
+        let mut decompressed = Vec::new();
+        let mut chunk = [0; 100];
+        loop {
+            let num_bytes = decompressor.read(&mut chunk).unwrap();
+            if num_bytes == 0 {
+                break;
+            }
+            decompressed.extend_from_slice(&chunk[..num_bytes]);
+            assert!(decompressor.output.recent.len() <= MAX_RELATIVE_DISTANCE + 0x400);
+        }
+        assert!(decompressed.len() > MAX_ABSOLUTE_REFERENCE);
+        assert_eq!(
+            decompressed,
+            decompress(&source, CompressionType::LZ5).unwrap()
+        );
+    }
+
+    #[test]
+    fn decompressor_keeps_lz3_backwards_history() {
+        let mut source = Vec::new();
+        for i in 0..66 {
+            // Long form byte fills of 1024 bytes.
+            source.extend([0b11100111, 0xFF, i]);
+        }
+        source.extend([0b00000011, 1, 2, 3, 4]);
+        // A long form backwards repeat of 512 bytes, starting 2 bytes back.
+        source.extend([0b11111001, 0xFF, 0x81]);
+        source.push(0xFF);
+        let mut decompressor = Decompressor::new(source.as_slice(), CompressionType::LZ3);
+        let mut decompressed = Vec::new();
+        let mut chunk = [0; 100];
+        loop {
+            let num_bytes = decompressor.read(&mut chunk).unwrap();
+            if num_bytes == 0 {
+                break;
+            }
+            decompressed.extend_from_slice(&chunk[..num_bytes]);
+        }
+        assert_eq!(decompressed.len(), 68100);
+        assert_eq!(
+            decompressed,
+            decompress(&source, CompressionType::LZ3).unwrap()
+        );
+    }
+
+    #[test]
+    fn decompressor_stops_at_terminator() {
+        let source = vec![0b00000001, 0xA1, 0xA2, 0xFF, 0xAA];
+        let mut decompressed = Vec::new();
+        Decompressor::new(source.as_slice(), CompressionType::LZ5)
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(
+            decompressed,
+            decompress(&source, CompressionType::LZ5).unwrap()
+        );
+    }
+
+    #[test]
+    fn decompressor_returns_invalid_data_error() {
+        let source = vec![0b00000011, 0xA1, 0xA2];
+        let mut decompressed = Vec::new();
+        let error = Decompressor::new(source.as_slice(), CompressionType::LZ5)
+            .read_to_end(&mut decompressed)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decompressor_reports_offset_of_failed_command() {
+        let source = vec![0b00100011, 0xA1, 0b00000011, 0xA2];
+        let mut decompressed = Vec::new();
+        let error = Decompressor::new(source.as_slice(), CompressionType::LZ5)
+            .read_to_end(&mut decompressed)
+            .unwrap_err();
+        let kind = DecompressionErrorKind::IndexOutOfBounds;
+        let expected = DecompressionError::new(kind, 2, Some(0b00000011), 4);
+        let error = error.into_inner().unwrap().downcast::<DecompressionError>();
+        assert_eq!(*error.unwrap(), expected);
+    }
+
+    #[test]
+    fn decompressor_honors_framing() {
+        let source = get_test_data(0x3000);
+        let framings = [Framing::OutputSize(source.len()), Framing::SizePrefix];
+        for compression_type in [CompressionType::LZ5, CompressionType::RLE1] {
+            for framing in framings {
+                let options = crate::CompressionOptions::new().set_framing(framing);
+                let mut compressed =
+                    crate::compress_with_options(&source, compression_type, &options).unwrap();
+                // Data after the stream is left alone.
+                compressed.extend([0xAA; 8]);
+                let reader = ChunkedReader {
+                    source: &compressed,
+                    chunk_size: 7,
+                };
+                let options = DecompressionOptions::new().set_framing(framing);
+                let mut decompressed = Vec::new();
+                Decompressor::with_options(reader, compression_type, options)
+                    .read_to_end(&mut decompressed)
+                    .unwrap();
+                assert_eq!(decompressed, source, "{compression_type} {framing:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::{
+    build_error, get_decompression_strategy, process_next, read_command_header,
+    DecompressionOptions, StreamError, StreamResult,
+};
+use crate::errors::DecompressionError;
+use crate::CompressionType;
+
+/// A single command read from a compressed stream.
+#[derive(Debug, PartialEq)]
+pub struct DisassembledCommand {
+    /// The offset of the command header in the compressed data.
+    pub offset: usize,
+    /// The command number, read from the short form bits or from the long form ones after the
+    /// marker.
+    pub cmd_num: u8,
+    /// Whether the command has the two byte long form header.
+    pub is_extended: bool,
+    /// The number of bytes the command writes.
+    pub cmd_size: usize,
+    /// The bytes following the command header.
+    pub arguments: Vec<u8>,
+    /// The range of the decompressed data written by the command.
+    pub output_range: Range<usize>,
+}
+
+/// Decodes `source` into the commands it is made of, stopping at the terminator.
+///
+/// Only the LZ formats are made of commands. Any other format returns an
+/// [`UnsupportedFormat`](crate::errors::DecompressionErrorKind::UnsupportedFormat) error.
+pub fn disassemble(
+    source: &[u8],
+    compression_type: CompressionType,
+) -> Result<Vec<DisassembledCommand>, DecompressionError> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let options = DecompressionOptions::default();
+    disassemble_commands(source, &mut buffer, compression_type)
+        .map_err(|error| build_error(source, &buffer, error, &options))
+}
+
+fn disassemble_commands(
+    source: &[u8],
+    buffer: &mut Vec<u8>,
+    compression_type: CompressionType,
+) -> StreamResult<Vec<DisassembledCommand>> {
+    let strategy = get_decompression_strategy(compression_type)
+        .map_err(|kind| StreamError::new(kind, 0, None))?;
+    let mut commands = Vec::new();
+    let mut i = 0;
+    while i < source.len() {
+        let output_start = buffer.len();
+        let command_outcome =
+            process_next(&source[i..], buffer, &strategy, Some(strategy.terminator()))
+                .map_err(|kind| StreamError::at(source, i, kind))?;
+        if command_outcome.is_terminated {
+            break;
+        }
+
+        let header = read_command_header(&source[i..], strategy.num_cmd_bits())
+            .map_err(|kind| StreamError::at(source, i, kind))?;
+        let command_end = i + command_outcome.num_bytes_consumed;
+        commands.push(DisassembledCommand {
+            offset: i,
+            cmd_num: header.cmd_bits,
+            is_extended: header.is_extended,
+            cmd_size: header.cmd_size,
+            arguments: source[i + header.len()..command_end].to_vec(),
+            output_range: output_start..buffer.len(),
+        });
+        i = command_end;
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassemble_works() {
+        let source = vec![0b00000001, 0xA1, 0xA2, 0b11111100, 0x01, 0x02, 0xFF, 0xAA];
+        let commands = disassemble(&source, CompressionType::LZ5).unwrap();
+        let expected = vec![
+            DisassembledCommand {
+                offset: 0,
+                cmd_num: 0b000,
+                is_extended: false,
+                cmd_size: 2,
+                arguments: vec![0xA1, 0xA2],
+                output_range: 0..2,
+            },
+            DisassembledCommand {
+                offset: 3,
+                cmd_num: 0b111,
+                is_extended: true,
+                cmd_size: 2,
+                arguments: vec![0x02],
+                output_range: 2..4,
+            },
+        ];
+        assert_eq!(commands, expected);
+    }
+
+    #[test]
+    fn disassemble_returns_error_for_rle() {
+        let source = vec![0b10000001, 0xAA, 0xFF];
+        assert!(disassemble(&source, CompressionType::RLE1).is_err());
+    }
+}
@@ -1,12 +1,92 @@
-mod command_callbacks;
+pub mod command_callbacks;
+#[cfg(feature = "std")]
+mod decompressor;
+mod disassembler;
+mod options;
+mod output_buffer;
+mod rle;
 mod stragies;
 
-use self::{command_callbacks::CommandCallback, stragies::DecompressionStrategy};
+use alloc::vec::Vec;
+
+use self::{command_callbacks::CommandCallback, output_buffer::SliceBuffer};
+#[cfg(feature = "std")]
+pub use decompressor::Decompressor;
+pub use disassembler::{disassemble, DisassembledCommand};
+pub use options::DecompressionOptions;
+pub use output_buffer::OutputBuffer;
+pub use stragies::DecompressionStrategy;
 
 use super::CompressionType;
 use crate::errors::{DecompressionError, DecompressionErrorInfo, DecompressionErrorKind};
+use crate::framing::{self, Framing};
 
 type DecompResult<T> = core::result::Result<T, DecompressionErrorKind>;
+type StreamResult<T> = core::result::Result<T, StreamError>;
+
+/// Where a stream failed. The output length and buffers are added by [`build_error`].
+#[derive(Debug, PartialEq)]
+struct StreamError {
+    kind: DecompressionErrorKind,
+    offset: usize,
+    command_byte: Option<u8>,
+}
+
+impl StreamError {
+    fn new(kind: DecompressionErrorKind, offset: usize, command_byte: Option<u8>) -> Self {
+        StreamError {
+            kind,
+            offset,
+            command_byte,
+        }
+    }
+
+    /// An error in the command starting at `offset` in `source`.
+    fn at(source: &[u8], offset: usize, kind: DecompressionErrorKind) -> Self {
+        StreamError::new(kind, offset, source.get(offset).copied())
+    }
+}
+
+/// The result of decompressing a stream, along with details about the compressed data.
+#[derive(Debug, PartialEq)]
+pub struct DecompressionInfo {
+    /// The decompressed data.
+    pub data: Vec<u8>,
+    /// The number of compressed bytes read, including the terminator.
+    pub num_bytes_consumed: usize,
+    /// The number of commands read, not counting the terminator.
+    pub num_commands: usize,
+    /// Whether the stream ended with a terminator, or reached its expected size, rather than
+    /// running out of data.
+    pub is_terminated: bool,
+}
+
+impl DecompressionInfo {
+    fn new(data: Vec<u8>, stream_outcome: StreamOutcome) -> Self {
+        DecompressionInfo {
+            data,
+            num_bytes_consumed: stream_outcome.num_bytes_consumed,
+            num_commands: stream_outcome.num_commands,
+            is_terminated: stream_outcome.is_terminated,
+        }
+    }
+}
+
+struct StreamOutcome {
+    is_terminated: bool,
+    num_bytes_consumed: usize,
+    num_commands: usize,
+}
+
+impl StreamOutcome {
+    fn new(is_terminated: bool, num_bytes_consumed: usize, num_commands: usize) -> Self {
+        StreamOutcome {
+            is_terminated,
+            num_bytes_consumed,
+            num_commands,
+        }
+    }
+}
 
 struct CommandOutcome {
     is_terminated: bool,
@@ -25,47 +105,229 @@ impl CommandOutcome {
 pub fn decompress(
     source: &[u8],
     compression_type: CompressionType,
+) -> Result<Vec<u8>, DecompressionError> {
+    decompress_with_options(source, compression_type, &DecompressionOptions::default())
+}
+
+/// Decompresses `source` like [`decompress`], with the settings in `options`.
+pub fn decompress_with_options(
+    source: &[u8],
+    compression_type: CompressionType,
+    options: &DecompressionOptions,
 ) -> Result<Vec<u8>, DecompressionError> {
     let mut buffer: Vec<u8> = Vec::new();
-    let strategy = get_decompression_strategy(compression_type)
-        .map_err(|kind| build_error(source, &buffer, kind))?;
+    decompress_stream(source, &mut buffer, compression_type, options.framing)
+        .map_err(|error| build_error(source, &buffer, error, options))?;
+    Ok(buffer)
+}
+
+/// Decompresses `source` like [`decompress`], and also reports where the compressed stream ended.
+/// This is useful when compressed streams are stored back to back.
+pub fn decompress_with_info(
+    source: &[u8],
+    compression_type: CompressionType,
+) -> Result<DecompressionInfo, DecompressionError> {
+    decompress_info_with_options(source, compression_type, &DecompressionOptions::default())
+}
+
+fn decompress_info_with_options(
+    source: &[u8],
+    compression_type: CompressionType,
+    options: &DecompressionOptions,
+) -> Result<DecompressionInfo, DecompressionError> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let stream_outcome = decompress_stream(source, &mut buffer, compression_type, options.framing)
+        .map_err(|error| build_error(source, &buffer, error, options))?;
+    Ok(DecompressionInfo::new(buffer, stream_outcome))
+}
+
+/// Decompresses `source` into `destination` and returns the number of bytes written. The output
+/// is never allocated, so this suits fixed buffers such as a mirror of WRAM. If the output does
+/// not fit, an [`OutputOverflow`](DecompressionErrorKind::OutputOverflow) error is returned and
+/// `destination` holds the output up to that point.
+///
+/// RLE2 still decompresses its two bitplanes into temporary buffers before interleaving them.
+pub fn decompress_into(
+    source: &[u8],
+    destination: &mut [u8],
+    compression_type: CompressionType,
+) -> Result<usize, DecompressionError> {
+    let mut buffer = SliceBuffer::new(destination);
+    let options = DecompressionOptions::default();
+    decompress_stream(source, &mut buffer, compression_type, options.framing)
+        .map_err(|error| build_error(source, buffer.as_slice(), error, &options))?;
+    Ok(buffer.len())
+}
+
+/// Decompresses `source` with the commands in `strategy`, for formats that are not built in.
+pub fn decompress_with_strategy(
+    source: &[u8],
+    strategy: &DecompressionStrategy,
+    options: &DecompressionOptions,
+) -> Result<Vec<u8>, DecompressionError> {
+    let mut buffer: Vec<u8> = Vec::new();
+    decompress_framed(source, options.framing, |source, output_size| {
+        decompress_commands(source, &mut buffer, strategy, output_size)
+    })
+    .map_err(|error| build_error(source, &buffer, error, options))?;
+    Ok(buffer)
+}
+
+fn decompress_stream(
+    source: &[u8],
+    buffer: &mut dyn OutputBuffer,
+    compression_type: CompressionType,
+    framing: Framing,
+) -> StreamResult<StreamOutcome> {
+    match compression_type {
+        // Each plane ends with a terminator, so the planes cannot be framed any other way.
+        CompressionType::RLE2 if framing != Framing::Terminator => Err(StreamError::new(
+            DecompressionErrorKind::UnsupportedFraming,
+            0,
+            None,
+        )),
+        CompressionType::RLE2 => rle::decompress_rle2(source, buffer),
+        CompressionType::RLE1 => decompress_framed(source, framing, |source, output_size| {
+            rle::decompress_rle1(source, buffer, output_size)
+        }),
+        _ => {
+            let strategy = get_decompression_strategy(compression_type)
+                .map_err(|kind| StreamError::new(kind, 0, None))?;
+            decompress_framed(source, framing, |source, output_size| {
+                decompress_commands(source, buffer, &strategy, output_size)
+            })
+        }
+    }
+}
+
+/// Reads the size prefix, if `framing` has one, and decompresses the rest of `source` with
+/// `decompress_body`, which is given the expected output size when there is no terminator.
+fn decompress_framed(
+    source: &[u8],
+    framing: Framing,
+    decompress_body: impl FnOnce(&[u8], Option<usize>) -> StreamResult<StreamOutcome>,
+) -> StreamResult<StreamOutcome> {
+    match framing {
+        Framing::Terminator => decompress_body(source, None),
+        Framing::OutputSize(output_size) => decompress_body(source, Some(output_size)),
+        Framing::SizePrefix => {
+            let output_size = framing::read_size_prefix(source).ok_or(StreamError::new(
+                DecompressionErrorKind::IndexOutOfBounds,
+                0,
+                None,
+            ))?;
+            let prefix_len = framing::SIZE_PREFIX_LEN;
+            let stream_outcome = decompress_body(&source[prefix_len..], Some(output_size))
+                .map_err(|error| StreamError {
+                    offset: prefix_len + error.offset,
+                    ..error
+                })?;
+            Ok(StreamOutcome {
+                num_bytes_consumed: prefix_len + stream_outcome.num_bytes_consumed,
+                ..stream_outcome
+            })
+        }
+    }
+}
+
+/// Decompresses commands until the terminator, or until `output_size` bytes have been written if
+/// it is given.
+fn decompress_commands(
+    source: &[u8],
+    buffer: &mut dyn OutputBuffer,
+    strategy: &DecompressionStrategy,
+    output_size: Option<usize>,
+) -> StreamResult<StreamOutcome> {
+    let terminator = match output_size {
+        Some(_) => None,
+        None => Some(strategy.terminator()),
+    };
+    let mut num_commands = 0;
     let mut i = 0;
-    while i < source.len() {
-        let command_outcome = process_next(&source[i..], &mut buffer, &strategy)
-            .map_err(|kind| build_error(source, &buffer, kind))?;
+    while i < source.len() && !is_output_complete(buffer, output_size) {
+        let command_outcome = process_next(&source[i..], buffer, strategy, terminator)
+            .and_then(|command_outcome| {
+                check_output_size(buffer, output_size)?;
+                Ok(command_outcome)
+            })
+            .map_err(|kind| StreamError::at(source, i, kind))?;
+        i += command_outcome.num_bytes_consumed;
         if command_outcome.is_terminated {
-            break;
-        } else {
-            i += command_outcome.num_bytes_consumed;
+            return Ok(StreamOutcome::new(true, i, num_commands));
         }
+        num_commands += 1;
+    }
+    let is_complete = is_output_complete(buffer, output_size);
+    Ok(StreamOutcome::new(is_complete, i, num_commands))
+}
+
+fn is_output_complete(buffer: &dyn OutputBuffer, output_size: Option<usize>) -> bool {
+    output_size.is_some_and(|output_size| buffer.len() >= output_size)
+}
+
+/// Fails if a command wrote past the expected output size.
+fn check_output_size(buffer: &dyn OutputBuffer, output_size: Option<usize>) -> DecompResult<()> {
+    match output_size {
+        Some(output_size) if buffer.len() > output_size => {
+            Err(DecompressionErrorKind::OutputOverflow)
+        }
+        _ => Ok(()),
     }
-    Ok(buffer)
 }
 
+/// Decompresses the command at the start of `source`. Without a terminator, every byte starts a
+/// command.
 fn process_next(
     source: &[u8],
-    buffer: &mut Vec<u8>,
+    buffer: &mut dyn OutputBuffer,
     strategy: &DecompressionStrategy,
+    terminator: Option<u8>,
 ) -> DecompResult<CommandOutcome> {
-    let first_byte = source[0];
-    if first_byte == 0xFF {
+    let &first_byte = source
+        .first()
+        .ok_or(DecompressionErrorKind::IndexOutOfBounds)?;
+    if Some(first_byte) == terminator {
         Ok(CommandOutcome::new(true, 1))
     } else {
-        let is_extended_cmd = is_extended_cmd(first_byte);
-        let cmd_bits = get_command_bits(first_byte, is_extended_cmd);
-        let cmd_size = get_command_size(source, is_extended_cmd)?;
-        let source_offset = get_command_source_offset(source, is_extended_cmd)?;
-        let cmd_callback = get_command_callback(is_extended_cmd, cmd_bits, &strategy)?;
-        let num_skip = cmd_callback(source_offset, buffer, cmd_size)?;
-
-        if is_extended_cmd {
-            Ok(CommandOutcome::new(false, num_skip + 2))
+        let header = read_command_header(source, strategy.num_cmd_bits())?;
+        let source_offset = get_command_source_offset(source, header.is_extended)?;
+        let cmd_callback = get_command_callback(header.is_extended, header.cmd_bits, strategy)?;
+        let num_skip = cmd_callback(source_offset, buffer, header.cmd_size)?;
+        Ok(CommandOutcome::new(false, num_skip + header.len()))
+    }
+}
+
+/// The fields of a command header, in the short or the long form.
+struct CommandHeader {
+    cmd_bits: u8,
+    is_extended: bool,
+    cmd_size: usize,
+}
+
+impl CommandHeader {
+    /// The number of bytes of the header.
+    fn len(&self) -> usize {
+        if self.is_extended {
+            2
         } else {
-            Ok(CommandOutcome::new(false, num_skip + 1))
+            1
         }
     }
 }
 
+/// Reads the header at the start of `source`, which must not be the terminator.
+fn read_command_header(source: &[u8], num_cmd_bits: usize) -> DecompResult<CommandHeader> {
+    let &first_byte = source
+        .first()
+        .ok_or(DecompressionErrorKind::IndexOutOfBounds)?;
+    let is_extended = is_extended_cmd(first_byte, num_cmd_bits);
+    Ok(CommandHeader {
+        cmd_bits: get_command_bits(first_byte, is_extended, num_cmd_bits),
+        is_extended,
+        cmd_size: get_command_size(source, is_extended, num_cmd_bits)?,
+    })
+}
+
 fn get_command_callback(
     is_extended: bool,
     cmd_bits: u8,
@@ -77,34 +339,40 @@ fn get_command_callback(
     Ok(callback)
 }
 
-fn is_extended_cmd(byte: u8) -> bool {
-    get_command_bits(byte, false) == 0b111
+/// The short form command number made of all ones marks the long form.
+fn is_extended_cmd(byte: u8, num_cmd_bits: usize) -> bool {
+    get_command_bits(byte, false, num_cmd_bits) == get_bit_mask(num_cmd_bits)
 }
 
-fn get_command_bits(byte: u8, extended: bool) -> u8 {
+fn get_command_bits(byte: u8, extended: bool, num_cmd_bits: usize) -> u8 {
     if extended {
-        (byte & 0b00011100) >> 2
+        (byte >> (8 - 2 * num_cmd_bits)) & get_bit_mask(num_cmd_bits)
     } else {
-        (byte & 0b11100000) >> 5
+        byte >> (8 - num_cmd_bits)
     }
 }
 
-fn get_command_size(source: &[u8], extended: bool) -> DecompResult<usize> {
+fn get_command_size(source: &[u8], extended: bool, num_cmd_bits: usize) -> DecompResult<usize> {
     let required_size = 1_usize + extended as usize;
     if source.len() < required_size {
         Err(DecompressionErrorKind::IndexOutOfBounds)
     } else {
         let cmd_size = if extended {
-            let first_byte: usize = (source[0] & 0b00000011).into();
+            let first_byte: usize = (source[0] & get_bit_mask(8 - 2 * num_cmd_bits)).into();
             let second_byte: usize = (source[1]).into();
             (first_byte << 8) | second_byte
         } else {
-            (source[0] & 0b00011111).into()
+            (source[0] & get_bit_mask(8 - num_cmd_bits)).into()
         };
         Ok(cmd_size + 1)
     }
 }
 
+/// Returns a byte with the lowest `num_bits` bits set.
+fn get_bit_mask(num_bits: usize) -> u8 {
+    ((1_u16 << num_bits) - 1) as u8
+}
+
 fn get_command_source_offset(source: &[u8], extended: bool) -> DecompResult<&[u8]> {
     let offset = if extended { 2 } else { 1 };
     if offset > source.len() {
@@ -124,11 +392,17 @@ fn get_decompression_strategy(
 
 fn build_error(
     source: &[u8],
-    buffer: &Vec<u8>,
-    kind: DecompressionErrorKind,
+    buffer: &[u8],
+    error: StreamError,
+    options: &DecompressionOptions,
 ) -> DecompressionError {
-    let error_info = DecompressionErrorInfo::new(source, &buffer);
-    DecompressionError::new(kind, error_info)
+    let decompression_error =
+        DecompressionError::new(error.kind, error.offset, error.command_byte, buffer.len());
+    if options.keep_error_buffers {
+        decompression_error.set_error_info(DecompressionErrorInfo::new(source, buffer))
+    } else {
+        decompression_error
+    }
 }
 
 #[cfg(test)]
@@ -141,6 +415,26 @@ mod tests {
         assert_eq!(result, vec![]);
     }
 
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "command 16 does not fit in 4 bits")]
+    fn rejects_commands_wider_than_the_header() {
+        let _ = DecompressionStrategy::new()
+            .set_num_cmd_bits(4)
+            .insert_command(16, false, command_callbacks::direct_copy);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "command 8 does not fit in 3 bits")]
+    fn rejects_commands_before_widening_the_header() {
+        let _ = DecompressionStrategy::new().insert_command(
+            0b1000,
+            false,
+            command_callbacks::direct_copy,
+        );
+    }
+
     #[test]
     fn direct_copy_works() {
         let first_byte = 0b00000010;
@@ -180,4 +474,444 @@ mod tests {
             Err(_) => panic!("Word fill failed"),
         };
     }
+
+    #[test]
+    fn repeat_be_works() {
+        let first_byte = 0b10000010;
+        let source = vec![0b00000001, 0xA1, 0xA2, first_byte, 0x00, 0x00, 0xFF];
+        match decompress(&source, CompressionType::LZ2) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xA1, 0xA2, 0xA1, 0xA2, 0xA1]),
+            Err(_) => panic!("Repeat be failed"),
+        };
+    }
+
+    #[test]
+    fn xor_repeat_be_works() {
+        let first_byte = 0b10100001;
+        let source = vec![0b00000001, 0xA1, 0xA2, first_byte, 0x00, 0x00, 0xFF];
+        match decompress(&source, CompressionType::LZ4) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xA1, 0xA2, 0x5E, 0x5D]),
+            Err(_) => panic!("Xor repeat be failed"),
+        };
+    }
+
+    #[test]
+    fn zero_fill_works() {
+        let first_byte = 0b01100010;
+        let source = vec![first_byte, 0xFF];
+        match decompress(&source, CompressionType::LZ3) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0x00, 0x00, 0x00]),
+            Err(_) => panic!("Zero fill failed"),
+        };
+    }
+
+    #[test]
+    fn signed_repeat_works() {
+        let first_byte = 0b10000010;
+        let source = vec![
+            0b00000001, 0xA1, 0xA2, first_byte, 0x81, first_byte, 0x00, 0x01, 0xFF,
+        ];
+        match decompress(&source, CompressionType::LZ3) {
+            Ok(decompressed) => assert_eq!(
+                decompressed,
+                vec![0xA1, 0xA2, 0xA1, 0xA2, 0xA1, 0xA2, 0xA1, 0xA2]
+            ),
+            Err(_) => panic!("Signed repeat failed"),
+        };
+    }
+
+    #[test]
+    fn signed_bit_reversed_repeat_works() {
+        let first_byte = 0b10100001;
+        let source = vec![0b00000001, 0x01, 0x03, first_byte, 0x81, 0xFF];
+        match decompress(&source, CompressionType::LZ3) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0x01, 0x03, 0x80, 0xC0]),
+            Err(_) => panic!("Signed bit reversed repeat failed"),
+        };
+    }
+
+    #[test]
+    fn signed_backwards_repeat_works() {
+        let first_byte = 0b11000010;
+        let source = vec![0b00000010, 0xA1, 0xA2, 0xA3, first_byte, 0x80, 0xFF];
+        match decompress(&source, CompressionType::LZ3) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xA1, 0xA2, 0xA3, 0xA3, 0xA2, 0xA1]),
+            Err(_) => panic!("Signed backwards repeat failed"),
+        };
+    }
+
+    #[test]
+    fn lz19_direct_copy_works() {
+        let first_byte = 0b00000010;
+        let source = vec![first_byte, 0xA1, 0xA2, 0xA3, 0xFF];
+        match decompress(&source, CompressionType::LZ19) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xA1, 0xA2, 0xA3]),
+            Err(_) => panic!("LZ19 direct copy failed"),
+        };
+    }
+
+    #[test]
+    fn lz19_byte_fill_works() {
+        let first_byte = 0b00100010;
+        let source = vec![first_byte, 0xA1, 0xFF];
+        match decompress(&source, CompressionType::LZ19) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xA1, 0xA1, 0xA1]),
+            Err(_) => panic!("LZ19 byte fill failed"),
+        };
+    }
+
+    #[test]
+    fn lz19_word_fill_works() {
+        let first_byte = 0b01000011;
+        let source = vec![first_byte, 0xAA, 0xBB, 0xFF];
+        match decompress(&source, CompressionType::LZ19) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xAA, 0xBB, 0xAA, 0xBB]),
+            Err(_) => panic!("LZ19 word fill failed"),
+        };
+    }
+
+    #[test]
+    fn lz19_zero_fill_works() {
+        let first_byte = 0b01100011;
+        let source = vec![first_byte, 0xFF];
+        match decompress(&source, CompressionType::LZ19) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0x00, 0x00, 0x00, 0x00]),
+            Err(_) => panic!("LZ19 zero fill failed"),
+        };
+    }
+
+    #[test]
+    fn lz19_repeat_le_works() {
+        let first_byte = 0b10000010;
+        let source = vec![0b00000001, 0xA1, 0xA2, first_byte, 0x00, 0x00, 0xFF];
+        match decompress(&source, CompressionType::LZ19) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xA1, 0xA2, 0xA1, 0xA2, 0xA1]),
+            Err(_) => panic!("LZ19 repeat le failed"),
+        };
+    }
+
+    #[test]
+    fn lz19_xor_repeat_le_works() {
+        let first_byte = 0b10100001;
+        let source = vec![0b00000001, 0xA1, 0xA2, first_byte, 0x00, 0x00, 0xFF];
+        match decompress(&source, CompressionType::LZ19) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xA1, 0xA2, 0x5E, 0x5D]),
+            Err(_) => panic!("LZ19 xor repeat le failed"),
+        };
+    }
+
+    #[test]
+    fn lz19_negative_repeat_works() {
+        let first_byte = 0b11000010;
+        let source = vec![0b00000001, 0xA1, 0xA2, first_byte, 0x02, 0xFF];
+        match decompress(&source, CompressionType::LZ19) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xA1, 0xA2, 0xA1, 0xA2, 0xA1]),
+            Err(_) => panic!("LZ19 negative repeat failed"),
+        };
+    }
+
+    #[test]
+    fn lz19_negative_xor_repeat_works() {
+        let first_byte = 0b11111100;
+        let source = vec![0b00000001, 0xA1, 0xA2, first_byte, 0x01, 0x02, 0xFF];
+        match decompress(&source, CompressionType::LZ19) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xA1, 0xA2, 0x5E, 0x5D]),
+            Err(_) => panic!("LZ19 negative xor repeat failed"),
+        };
+    }
+
+    #[test]
+    fn increasing_fill_wraps_around() {
+        let first_byte = 0b01100011;
+        let source = vec![first_byte, 0xFE, 0xFF];
+        match decompress(&source, CompressionType::LZ5) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xFE, 0xFF, 0x00, 0x01]),
+            Err(_) => panic!("Increasing fill failed"),
+        };
+    }
+
+    #[test]
+    fn long_increasing_fill_wraps_around_more_than_once() {
+        // A long form increasing fill of 0x201 bytes, starting at 0xFF.
+        let source = vec![0b11101110, 0x00, 0xFF, 0xFF];
+        let decompressed = decompress(&source, CompressionType::LZ5).unwrap();
+        let expected: Vec<u8> = (0..0x201_u32).map(|i| (0xFF + i) as u8).collect();
+        assert_eq!(decompressed, expected);
+    }
+
+    #[test]
+    fn truncated_direct_copy_returns_error() {
+        let first_byte = 0b00000010;
+        let source = vec![first_byte, 0xA1, 0xA2];
+        let kind = DecompressionErrorKind::IndexOutOfBounds;
+        let expected = DecompressionError::new(kind, 0, Some(first_byte), 0);
+        assert_eq!(decompress(&source, CompressionType::LZ5), Err(expected));
+    }
+
+    #[test]
+    fn repeat_past_end_of_buffer_returns_error() {
+        let first_byte = 0b10000010;
+        let source = vec![0b00000000, 0xA1, first_byte, 0x02, 0x00, 0xFF];
+        let kind = DecompressionErrorKind::InvalidOffset;
+        let expected = DecompressionError::new(kind, 2, Some(first_byte), 1);
+        assert_eq!(decompress(&source, CompressionType::LZ5), Err(expected));
+    }
+
+    #[test]
+    fn negative_repeat_before_start_of_buffer_returns_error() {
+        let first_byte = 0b11000010;
+        let source = vec![0b00000000, 0xA1, first_byte, 0x02, 0xFF];
+        let kind = DecompressionErrorKind::InvalidOffset;
+        let expected = DecompressionError::new(kind, 2, Some(first_byte), 1);
+        assert_eq!(decompress(&source, CompressionType::LZ5), Err(expected));
+    }
+
+    #[test]
+    fn signed_backwards_repeat_before_start_of_buffer_returns_error() {
+        let first_byte = 0b11000010;
+        let source = vec![0b00000000, 0xA1, first_byte, 0x00, 0x00, 0xFF];
+        let kind = DecompressionErrorKind::InvalidOffset;
+        let expected = DecompressionError::new(kind, 2, Some(first_byte), 2);
+        assert_eq!(decompress(&source, CompressionType::LZ3), Err(expected));
+    }
+
+    #[test]
+    fn arbitrary_input_does_not_panic() {
+        let compression_types = [
+            CompressionType::LZ1,
+            CompressionType::LZ2,
+            CompressionType::LZ3,
+            CompressionType::LZ4,
+            CompressionType::LZ5,
+            CompressionType::LZ19,
+            CompressionType::RLE1,
+            CompressionType::RLE2,
+        ];
+        let mut state: u32 = 0x12345678;
+        for _ in 0..2000 {
+            let len = (state % 48) as usize;
+            let source: Vec<u8> = (0..len)
+                .map(|_| {
+                    state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                    (state >> 24) as u8
+                })
+                .collect();
+            for compression_type in compression_types {
+                let _ = decompress(&source, compression_type);
+            }
+        }
+    }
+
+    #[test]
+    fn decompress_with_info_stops_at_terminator() {
+        let first_byte = 0b00100010;
+        let source = vec![first_byte, 0xA1, 0b00000000, 0xA2, 0xFF, 0xAA, 0xBB];
+        let info = decompress_with_info(&source, CompressionType::LZ5).unwrap();
+        assert_eq!(info.data, vec![0xA1, 0xA1, 0xA1, 0xA2]);
+        assert_eq!(info.num_bytes_consumed, 5);
+        assert_eq!(info.num_commands, 2);
+        assert!(info.is_terminated);
+    }
+
+    #[test]
+    fn decompress_with_info_reports_missing_terminator() {
+        let first_byte = 0b00100010;
+        let source = vec![first_byte, 0xA1];
+        let info = decompress_with_info(&source, CompressionType::LZ5).unwrap();
+        assert_eq!(info.data, vec![0xA1, 0xA1, 0xA1]);
+        assert_eq!(info.num_bytes_consumed, 2);
+        assert_eq!(info.num_commands, 1);
+        assert!(!info.is_terminated);
+    }
+
+    #[test]
+    fn decompress_with_info_works_for_rle2() {
+        let source = vec![0b10000001, 0xAA, 0xFF, 0b00000001, 0x01, 0x02, 0xFF, 0xAA];
+        let info = decompress_with_info(&source, CompressionType::RLE2).unwrap();
+        assert_eq!(info.data, vec![0xAA, 0x01, 0xAA, 0x02]);
+        assert_eq!(info.num_bytes_consumed, 7);
+        assert_eq!(info.num_commands, 2);
+        assert!(info.is_terminated);
+    }
+
+    #[test]
+    fn every_extended_command_works() {
+        let prefix = [0b00000001, 0xA1, 0xA2];
+        let cases: [(u8, Vec<u8>, Vec<u8>); 8] = [
+            (0b000, vec![0xB1, 0xB2, 0xB3], vec![0xB1, 0xB2, 0xB3]),
+            (0b001, vec![0xB1], vec![0xB1, 0xB1, 0xB1]),
+            (0b010, vec![0xB1, 0xB2], vec![0xB1, 0xB2, 0xB1]),
+            (0b011, vec![0x01], vec![0x01, 0x02, 0x03]),
+            (0b100, vec![0x00, 0x00], vec![0xA1, 0xA2, 0xA1]),
+            (0b101, vec![0x00, 0x00], vec![0x5E, 0x5D, 0xA1]),
+            (0b110, vec![0x02], vec![0xA1, 0xA2, 0xA1]),
+            (0b111, vec![0x02], vec![0x5E, 0x5D, 0xA1]),
+        ];
+        for (cmd_num, arguments, expected) in cases {
+            let mut source = prefix.to_vec();
+            source.push(0b11100000 | (cmd_num << 2));
+            source.push(0x02);
+            source.extend(arguments);
+            source.push(0xFF);
+            match decompress(&source, CompressionType::LZ5) {
+                Ok(decompressed) => assert_eq!(decompressed[2..], expected),
+                Err(_) => panic!("Extended command {cmd_num:03b} failed"),
+            };
+        }
+    }
+
+    #[test]
+    fn extended_command_supports_maximum_size() {
+        let source = vec![0b11100111, 0xFF, 0xAA, 0xFF];
+        match decompress(&source, CompressionType::LZ5) {
+            Ok(decompressed) => assert_eq!(decompressed, vec![0xAA; 1024]),
+            Err(_) => panic!("Maximum size byte fill failed"),
+        };
+    }
+
+    #[test]
+    fn missing_extended_command_returns_error() {
+        let source = vec![0b11111100, 0x00, 0x01, 0xFF];
+        let kind = DecompressionErrorKind::InvalidCommand;
+        let expected = DecompressionError::new(kind, 0, Some(0b11111100), 0);
+        assert_eq!(decompress(&source, CompressionType::LZ2), Err(expected));
+    }
+
+    #[test]
+    fn error_reports_kind_and_location() {
+        let source = vec![0b00100011, 0xA1, 0b11111100, 0x00, 0x01, 0xFF];
+        let error = decompress(&source, CompressionType::LZ2).unwrap_err();
+        assert_eq!(error.kind(), DecompressionErrorKind::InvalidCommand);
+        assert_eq!(error.offset(), 2);
+        assert_eq!(error.command_byte(), Some(0b11111100));
+        assert_eq!(error.output_len(), 4);
+        assert_eq!(error.error_info(), None);
+        assert_eq!(
+            error.to_string(),
+            "Decompression failed: invalid command at offset 0x2 (command byte 0xFC) after 0x4 \
+             bytes of output."
+        );
+    }
+
+    #[test]
+    fn unsupported_format_error_has_no_command_byte() {
+        let error = disassemble(&[0xFF], CompressionType::RLE1).unwrap_err();
+        assert_eq!(error.kind(), DecompressionErrorKind::UnsupportedFormat);
+        assert_eq!(error.command_byte(), None);
+        assert_eq!(
+            error.to_string(),
+            "Decompression failed: unsupported format at offset 0x0 after 0x0 bytes of output."
+        );
+    }
+
+    #[test]
+    fn error_buffers_are_opt_in() {
+        let source = vec![0b00100011, 0xA1, 0b00000010, 0xA2];
+        let options = DecompressionOptions::new().set_error_buffers(true);
+        let error = decompress_with_options(&source, CompressionType::LZ5, &options).unwrap_err();
+        let expected = DecompressionErrorInfo::new(&source, &[0xA1; 4]);
+        assert_eq!(error.error_info(), Some(&expected));
+        let error = decompress(&source, CompressionType::LZ5).unwrap_err();
+        assert_eq!(error.error_info(), None);
+    }
+
+    #[test]
+    fn decompress_into_works() {
+        let source = vec![0b00100011, 0xA1, 0b00000001, 0xB1, 0xB2, 0xFF];
+        let mut destination = [0; 8];
+        let num_bytes = decompress_into(&source, &mut destination, CompressionType::LZ5).unwrap();
+        assert_eq!(num_bytes, 6);
+        assert_eq!(
+            destination,
+            [0xA1, 0xA1, 0xA1, 0xA1, 0xB1, 0xB2, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn decompress_into_fills_exact_size() {
+        let source = vec![0b00100011, 0xA1, 0b00000001, 0xB1, 0xB2, 0xFF];
+        let mut destination = [0; 6];
+        let num_bytes = decompress_into(&source, &mut destination, CompressionType::LZ5).unwrap();
+        assert_eq!(num_bytes, 6);
+        assert_eq!(destination, [0xA1, 0xA1, 0xA1, 0xA1, 0xB1, 0xB2]);
+    }
+
+    #[test]
+    fn decompress_into_returns_error_on_overflow() {
+        let source = vec![0b00100011, 0xA1, 0b00000001, 0xB1, 0xB2, 0xFF];
+        let mut destination = [0; 5];
+        let kind = DecompressionErrorKind::OutputOverflow;
+        let expected = DecompressionError::new(kind, 2, Some(0b00000001), 4);
+        let result = decompress_into(&source, &mut destination, CompressionType::LZ5);
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn decompress_into_works_for_rle() {
+        let source = vec![0b10000011, 0xA1, 0xFF, 0b10000010, 0xB1, 0xFF];
+        let mut destination = [0; 7];
+        let num_bytes = decompress_into(&source, &mut destination, CompressionType::RLE2).unwrap();
+        assert_eq!(num_bytes, 7);
+        assert_eq!(destination, [0xA1, 0xB1, 0xA1, 0xB1, 0xA1, 0xB1, 0xA1]);
+        let mut destination = [0; 6];
+        let result = decompress_into(&source, &mut destination, CompressionType::RLE2);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn output_size_framing_stops_at_size() {
+        let source = vec![0b00100011, 0xA1, 0b00000001, 0xB1, 0xB2, 0x12, 0x34];
+        let options = DecompressionOptions::new().set_framing(Framing::OutputSize(6));
+        let info = decompress_info_with_options(&source, CompressionType::LZ5, &options).unwrap();
+        assert_eq!(info.data, vec![0xA1, 0xA1, 0xA1, 0xA1, 0xB1, 0xB2]);
+        assert_eq!(info.num_bytes_consumed, 5);
+        assert!(info.is_terminated);
+
+        let options = DecompressionOptions::new().set_framing(Framing::OutputSize(5));
+        let kind = DecompressionErrorKind::OutputOverflow;
+        let expected = DecompressionError::new(kind, 2, Some(0b00000001), 6);
+        let result = decompress_with_options(&source, CompressionType::LZ5, &options);
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn output_size_framing_has_no_terminator() {
+        // Without a terminator, 0xFF is an RLE1 fill of 128 bytes.
+        let source = vec![0xFF, 0xAA];
+        let options = DecompressionOptions::new().set_framing(Framing::OutputSize(0x80));
+        let result = decompress_with_options(&source, CompressionType::RLE1, &options);
+        assert_eq!(result, Ok(vec![0xAA; 0x80]));
+    }
+
+    #[test]
+    fn size_prefix_framing_works() {
+        let source = vec![0x06, 0x00, 0b00100011, 0xA1, 0b00000001, 0xB1, 0xB2];
+        let options = DecompressionOptions::new().set_framing(Framing::SizePrefix);
+        let info = decompress_info_with_options(&source, CompressionType::LZ2, &options).unwrap();
+        assert_eq!(info.data, vec![0xA1, 0xA1, 0xA1, 0xA1, 0xB1, 0xB2]);
+        assert_eq!(info.num_bytes_consumed, 7);
+        assert!(info.is_terminated);
+    }
+
+    #[test]
+    fn size_prefix_framing_reports_stream_offsets() {
+        let options = DecompressionOptions::new().set_framing(Framing::SizePrefix);
+        let source = vec![0x08, 0x00, 0b00100011, 0xA1, 0b11111100, 0x00, 0x01];
+        let error = decompress_with_options(&source, CompressionType::LZ2, &options).unwrap_err();
+        assert_eq!(error.kind(), DecompressionErrorKind::InvalidCommand);
+        assert_eq!(error.offset(), 4);
+
+        let kind = DecompressionErrorKind::IndexOutOfBounds;
+        let expected = DecompressionError::new(kind, 0, None, 0);
+        let result = decompress_with_options(&[0x08], CompressionType::LZ2, &options);
+        assert_eq!(result, Err(expected));
+    }
+
+    #[test]
+    fn rle2_only_supports_terminator_framing() {
+        let source = vec![0x02, 0x00, 0b10000000, 0xAA, 0xFF, 0b10000000, 0x01, 0xFF];
+        let options = DecompressionOptions::new().set_framing(Framing::SizePrefix);
+        let error = decompress_with_options(&source, CompressionType::RLE2, &options).unwrap_err();
+        assert_eq!(error.kind(), DecompressionErrorKind::UnsupportedFraming);
+    }
 }
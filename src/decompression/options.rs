@@ -0,0 +1,26 @@
+use crate::Framing;
+
+/// Settings for decompression.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DecompressionOptions {
+    /// Copies the compressed data and the partial output into errors. They can be large, so this
+    /// is off by default.
+    pub keep_error_buffers: bool,
+    pub framing: Framing,
+}
+
+impl DecompressionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_error_buffers(mut self, keep_error_buffers: bool) -> Self {
+        self.keep_error_buffers = keep_error_buffers;
+        self
+    }
+
+    pub fn set_framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+}
@@ -0,0 +1,101 @@
+use alloc::vec::Vec;
+
+use super::DecompResult;
+use crate::errors::DecompressionErrorKind;
+
+/// Where the commands write decompressed data.
+///
+/// Commands refer to earlier output by its absolute index, so an implementation only has to keep
+/// the bytes that can still be referenced.
+pub trait OutputBuffer {
+    /// The total number of bytes written so far.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the byte written at `index`, or `None` if it is no longer available.
+    fn byte_at(&self, index: usize) -> Option<u8>;
+
+    /// Writes `byte` after the rest of the output, or returns
+    /// [`OutputOverflow`](DecompressionErrorKind::OutputOverflow) if there is no room for it.
+    fn push(&mut self, byte: u8) -> DecompResult<()>;
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) -> DecompResult<()> {
+        for &byte in bytes {
+            self.push(byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl OutputBuffer for Vec<u8> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn byte_at(&self, index: usize) -> Option<u8> {
+        self.as_slice().get(index).copied()
+    }
+
+    fn push(&mut self, byte: u8) -> DecompResult<()> {
+        Vec::push(self, byte);
+        Ok(())
+    }
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) -> DecompResult<()> {
+        Vec::extend_from_slice(self, bytes);
+        Ok(())
+    }
+}
+
+/// Writes into a fixed slice, failing once it is full.
+pub struct SliceBuffer<'a> {
+    destination: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceBuffer<'a> {
+    pub fn new(destination: &'a mut [u8]) -> Self {
+        SliceBuffer {
+            destination,
+            len: 0,
+        }
+    }
+
+    /// The part of the slice that has been written.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.destination[..self.len]
+    }
+}
+
+impl OutputBuffer for SliceBuffer<'_> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn byte_at(&self, index: usize) -> Option<u8> {
+        self.as_slice().get(index).copied()
+    }
+
+    fn push(&mut self, byte: u8) -> DecompResult<()> {
+        let slot = self
+            .destination
+            .get_mut(self.len)
+            .ok_or(DecompressionErrorKind::OutputOverflow)?;
+        *slot = byte;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn extend_from_slice(&mut self, bytes: &[u8]) -> DecompResult<()> {
+        let end = self.len + bytes.len();
+        self.destination
+            .get_mut(self.len..end)
+            .ok_or(DecompressionErrorKind::OutputOverflow)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+}
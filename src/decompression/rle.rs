@@ -0,0 +1,172 @@
+use alloc::vec::Vec;
+
+use super::{
+    check_output_size, is_output_complete, output_buffer::OutputBuffer, DecompResult, StreamError,
+    StreamOutcome, StreamResult,
+};
+use crate::errors::DecompressionErrorKind;
+
+/// Decompresses a single RLE1 stream into `buffer`.
+///
+/// Each header byte is either `0LLLLLLL` (copy the next `L + 1` bytes) or `1LLLLLLL` (repeat the
+/// next byte `L + 1` times). The stream ends with `0xFF`, or once `output_size` bytes have been
+/// written if it is given.
+pub fn decompress_rle1(
+    source: &[u8],
+    buffer: &mut dyn OutputBuffer,
+    output_size: Option<usize>,
+) -> StreamResult<StreamOutcome> {
+    let mut num_commands = 0;
+    let mut i = 0;
+    while i < source.len() && !is_output_complete(buffer, output_size) {
+        if source[i] == 0xFF && output_size.is_none() {
+            return Ok(StreamOutcome::new(true, i + 1, num_commands));
+        }
+        num_commands += 1;
+        i += process_next(&source[i..], buffer)
+            .and_then(|num_bytes_consumed| {
+                check_output_size(buffer, output_size)?;
+                Ok(num_bytes_consumed)
+            })
+            .map_err(|kind| StreamError::at(source, i, kind))?;
+    }
+    let is_complete = is_output_complete(buffer, output_size);
+    Ok(StreamOutcome::new(is_complete, i, num_commands))
+}
+
+/// Decompresses the command at the start of `source`, returning the number of bytes it used.
+fn process_next(source: &[u8], buffer: &mut dyn OutputBuffer) -> DecompResult<usize> {
+    let header = source[0];
+    let size = (header & 0x7F) as usize + 1;
+    if header & 0x80 == 0 {
+        let bytes = source
+            .get(1..1 + size)
+            .ok_or(DecompressionErrorKind::IndexOutOfBounds)?;
+        buffer.extend_from_slice(bytes)?;
+        Ok(size + 1)
+    } else {
+        let &byte = source
+            .get(1)
+            .ok_or(DecompressionErrorKind::IndexOutOfBounds)?;
+        for _ in 0..size {
+            buffer.push(byte)?;
+        }
+        Ok(2)
+    }
+}
+
+/// Decompresses an RLE2 stream into `buffer`.
+///
+/// RLE2 stores the even bytes (the first bitplane of each 2bpp row) as one RLE1 stream, followed
+/// by the odd bytes (the second bitplane) as another. The two are interleaved back together.
+pub fn decompress_rle2(
+    source: &[u8],
+    buffer: &mut dyn OutputBuffer,
+) -> StreamResult<StreamOutcome> {
+    let mut even_plane = Vec::new();
+    let even_outcome = decompress_rle1(source, &mut even_plane, None)?;
+    let mut odd_plane = Vec::new();
+    let odd_start = even_outcome.num_bytes_consumed;
+    let odd_outcome =
+        decompress_rle1(&source[odd_start..], &mut odd_plane, None).map_err(|error| {
+            StreamError {
+                offset: odd_start + error.offset,
+                ..error
+            }
+        })?;
+
+    // Errors from here on are about the planes as a whole, so they point at the end of the stream.
+    let end_offset = odd_start + odd_outcome.num_bytes_consumed;
+    let stream_error = |kind| StreamError::new(kind, end_offset, None);
+    if even_plane.len() != odd_plane.len() && even_plane.len() != odd_plane.len() + 1 {
+        return Err(stream_error(DecompressionErrorKind::MismatchedPlaneSizes));
+    }
+    for (i, &byte) in even_plane.iter().enumerate() {
+        buffer.push(byte).map_err(stream_error)?;
+        if let Some(&odd_byte) = odd_plane.get(i) {
+            buffer.push(odd_byte).map_err(stream_error)?;
+        }
+    }
+    Ok(StreamOutcome::new(
+        even_outcome.is_terminated && odd_outcome.is_terminated,
+        end_offset,
+        even_outcome.num_commands + odd_outcome.num_commands,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rle1_direct_copy_works() {
+        let source = vec![0b00000010, 0xA1, 0xA2, 0xA3, 0xFF];
+        let mut buffer = Vec::new();
+        let stream_outcome = decompress_rle1(&source, &mut buffer, None).unwrap();
+        assert_eq!(buffer, vec![0xA1, 0xA2, 0xA3]);
+        assert_eq!(stream_outcome.num_bytes_consumed, 5);
+    }
+
+    #[test]
+    fn rle1_byte_fill_works() {
+        let source = vec![0b10000011, 0xA1, 0xFF];
+        let mut buffer = Vec::new();
+        let stream_outcome = decompress_rle1(&source, &mut buffer, None).unwrap();
+        assert_eq!(buffer, vec![0xA1, 0xA1, 0xA1, 0xA1]);
+        assert_eq!(stream_outcome.num_bytes_consumed, 3);
+    }
+
+    #[test]
+    fn rle1_returns_error_if_source_is_truncated() {
+        let source = vec![0b00000010, 0xA1];
+        let mut buffer = Vec::new();
+        let result = decompress_rle1(&source, &mut buffer, None);
+        let kind = DecompressionErrorKind::IndexOutOfBounds;
+        assert_eq!(
+            result.err(),
+            Some(StreamError::new(kind, 0, Some(0b00000010)))
+        );
+    }
+
+    #[test]
+    fn rle2_interleaves_planes() {
+        let source = vec![0b10000010, 0xAA, 0xFF, 0b00000010, 0x01, 0x02, 0x03, 0xFF];
+        let mut buffer = Vec::new();
+        decompress_rle2(&source, &mut buffer).unwrap();
+        assert_eq!(buffer, vec![0xAA, 0x01, 0xAA, 0x02, 0xAA, 0x03]);
+    }
+
+    #[test]
+    fn rle2_is_terminated_only_if_both_planes_are() {
+        let source = vec![0b00000000, 0xAA, 0xFF, 0xFF];
+        let mut buffer = Vec::new();
+        assert!(decompress_rle2(&source, &mut buffer).unwrap().is_terminated);
+
+        let source = vec![0b00000000, 0xAA];
+        let mut buffer = Vec::new();
+        let stream_outcome = decompress_rle2(&source, &mut buffer).unwrap();
+        assert!(!stream_outcome.is_terminated);
+        assert_eq!(buffer, vec![0xAA]);
+    }
+
+    #[test]
+    fn rle2_returns_error_if_plane_sizes_mismatch() {
+        let source = vec![0b10000010, 0xAA, 0xFF, 0b00000000, 0x01, 0xFF];
+        let mut buffer = Vec::new();
+        let result = decompress_rle2(&source, &mut buffer);
+        let kind = DecompressionErrorKind::MismatchedPlaneSizes;
+        assert_eq!(result.err(), Some(StreamError::new(kind, 6, None)));
+    }
+
+    #[test]
+    fn rle2_reports_odd_plane_errors_at_stream_offset() {
+        let source = vec![0b10000010, 0xAA, 0xFF, 0b00000010, 0x01];
+        let mut buffer = Vec::new();
+        let result = decompress_rle2(&source, &mut buffer);
+        let kind = DecompressionErrorKind::IndexOutOfBounds;
+        assert_eq!(
+            result.err(),
+            Some(StreamError::new(kind, 3, Some(0b00000010)))
+        );
+    }
+}
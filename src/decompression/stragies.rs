@@ -1,60 +1,166 @@
-use std::collections::HashMap;
-
 use super::command_callbacks as commands;
 use super::CompressionType;
 use commands::CommandCallback;
 
-type CommandMap = HashMap<u8, CommandCallback>;
+/// Maps each command number to its callback.
+type CommandTable = [Option<CommandCallback>; 1 << DecompressionStrategy::MAX_NUM_CMD_BITS];
 
+/// The commands of a format, split into the short form (`CCCLLLLL`) and the long form
+/// (`111CCCLL LLLLLLLL`). Short form `0b111` is the long form marker, so it is never looked up.
+///
+/// Those are the layouts for the default of 3 command bits. With `n` bits, the short form has `n`
+/// command bits and `8 - n` size bits, and the long form has `n` marker bits, `n` command bits
+/// and `16 - 2n` size bits.
+///
+/// Formats that are not built in can be described by inserting the callbacks in
+/// [`decompression_commands`](crate::decompression_commands), and decompressed with
+/// [`decompress_with_strategy`](crate::decompress_with_strategy).
+#[derive(Clone)]
 pub struct DecompressionStrategy {
-    command_map: CommandMap,
-    ext_command_map: CommandMap,
+    command_table: CommandTable,
+    ext_command_table: CommandTable,
+    num_cmd_bits: usize,
+    terminator: u8,
 }
 
 impl DecompressionStrategy {
+    /// The widest command number a header can hold.
+    pub const MAX_NUM_CMD_BITS: usize = 4;
+
     pub fn get_command_callback(&self, cmd_bits: u8, is_extended: bool) -> Option<CommandCallback> {
-        let temp = if is_extended && self.ext_command_map.contains_key(&cmd_bits) {
-            self.ext_command_map.get(&cmd_bits)
+        let table = if is_extended {
+            &self.ext_command_table
         } else {
-            self.command_map.get(&cmd_bits)
+            &self.command_table
         };
+        table.get(cmd_bits as usize).copied().flatten()
+    }
 
-        match temp {
-            Some(&callback) => Some(callback),
-            None => None,
-        }
+    pub fn num_cmd_bits(&self) -> usize {
+        self.num_cmd_bits
     }
 
-    fn new() -> DecompressionStrategy {
+    pub fn terminator(&self) -> u8 {
+        self.terminator
+    }
+
+    /// Creates a strategy without any commands, with 3 command bits and a `0xFF` terminator.
+    pub fn new() -> DecompressionStrategy {
         DecompressionStrategy {
-            command_map: CommandMap::new(),
-            ext_command_map: CommandMap::new(),
+            command_table: [None; 1 << Self::MAX_NUM_CMD_BITS],
+            ext_command_table: [None; 1 << Self::MAX_NUM_CMD_BITS],
+            num_cmd_bits: 3,
+            terminator: 0xFF,
         }
     }
 
-    fn insert_command(
+    /// Sets the width of the command number in a header, from 1 to
+    /// [`MAX_NUM_CMD_BITS`](DecompressionStrategy::MAX_NUM_CMD_BITS).
+    pub fn set_num_cmd_bits(mut self, num_cmd_bits: usize) -> Self {
+        self.num_cmd_bits = num_cmd_bits.clamp(1, Self::MAX_NUM_CMD_BITS);
+        self
+    }
+
+    /// Sets the byte that ends the stream. It is checked before a header is read.
+    pub fn set_terminator(mut self, terminator: u8) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    /// Registers `callback` for the long form of `command`, and for the short form as well unless
+    /// `is_extended_only` is set.
+    ///
+    /// `command` must fit in [`num_cmd_bits`](DecompressionStrategy::num_cmd_bits), so set those
+    /// first. A command that does not fit could never be read from a header, so it is ignored,
+    /// and debug builds panic.
+    pub fn insert_command(
         mut self,
         command: u8,
         is_extended_only: bool,
         callback: CommandCallback,
     ) -> Self {
-        if is_extended_only {
-            self.ext_command_map.insert(command, callback);
-        } else {
-            self.command_map.insert(command, callback);
+        let idx = command as usize;
+        debug_assert!(
+            idx < 1 << self.num_cmd_bits,
+            "command {command} does not fit in {} bits",
+            self.num_cmd_bits
+        );
+        if idx >= 1 << self.num_cmd_bits {
+            return self;
+        }
+        if !is_extended_only {
+            self.command_table[idx] = Some(callback);
         }
+        self.ext_command_table[idx] = Some(callback);
         self
     }
 }
 
+impl Default for DecompressionStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn get_decompression_strategy(
     compression_type: CompressionType,
 ) -> Option<DecompressionStrategy> {
     match compression_type {
+        CompressionType::LZ1 => Some(lz1_decomp_strategy()),
+        CompressionType::LZ2 => Some(lz2_decomp_strategy()),
+        CompressionType::LZ3 => Some(lz3_decomp_strategy()),
+        CompressionType::LZ4 => Some(lz4_decomp_strategy()),
         CompressionType::LZ5 => Some(lz5_decomp_strategy()),
+        CompressionType::LZ19 => Some(lz19_decomp_strategy()),
+        CompressionType::RLE1 | CompressionType::RLE2 => None,
     }
 }
 
+// https://github.com/bonimy/MushROMs/blob/master/doc/LC_LZ1%20Compression%20Format.md
+fn lz1_decomp_strategy() -> DecompressionStrategy {
+    DecompressionStrategy::new()
+        .insert_command(0b000, false, commands::direct_copy)
+        .insert_command(0b001, false, commands::byte_fill)
+        .insert_command(0b010, false, commands::word_fill)
+        .insert_command(0b011, false, commands::increasing_fill)
+        .insert_command(0b100, false, commands::repeat_be)
+}
+
+// https://github.com/bonimy/MushROMs/blob/master/doc/LC_LZ2%20Compression%20Format.md
+fn lz2_decomp_strategy() -> DecompressionStrategy {
+    DecompressionStrategy::new()
+        .insert_command(0b000, false, commands::direct_copy)
+        .insert_command(0b001, false, commands::byte_fill)
+        .insert_command(0b010, false, commands::word_fill)
+        .insert_command(0b011, false, commands::increasing_fill)
+        .insert_command(0b100, false, commands::repeat_be)
+}
+
+// https://github.com/pret/pokecrystal/blob/master/tools/lz/lz.h
+fn lz3_decomp_strategy() -> DecompressionStrategy {
+    DecompressionStrategy::new()
+        .insert_command(0b000, false, commands::direct_copy)
+        .insert_command(0b001, false, commands::byte_fill)
+        .insert_command(0b010, false, commands::word_fill)
+        .insert_command(0b011, false, commands::zero_fill)
+        .insert_command(0b100, false, commands::signed_repeat)
+        .insert_command(0b101, false, commands::signed_bit_reversed_repeat)
+        .insert_command(0b110, false, commands::signed_backwards_repeat)
+}
+
+// https://github.com/bonimy/MushROMs/blob/master/doc/LC_LZ4%20Compression%20Format.md
+fn lz4_decomp_strategy() -> DecompressionStrategy {
+    DecompressionStrategy::new()
+        .insert_command(0b000, false, commands::direct_copy)
+        .insert_command(0b001, false, commands::byte_fill)
+        .insert_command(0b010, false, commands::word_fill)
+        .insert_command(0b011, false, commands::increasing_fill)
+        .insert_command(0b100, false, commands::repeat_be)
+        .insert_command(0b101, false, commands::xor_repeat_be)
+        .insert_command(0b110, false, commands::negative_repeat)
+        .insert_command(0b111, true, commands::negative_xor_repeat_le)
+}
+
 // https://github.com/bonimy/MushROMs/blob/master/doc/LC_LZ5%20Compression%20Format.md
 fn lz5_decomp_strategy() -> DecompressionStrategy {
     DecompressionStrategy::new()
@@ -67,3 +173,16 @@ fn lz5_decomp_strategy() -> DecompressionStrategy {
         .insert_command(0b110, false, commands::negative_repeat)
         .insert_command(0b111, true, commands::negative_xor_repeat_le)
 }
+
+// Same as LZ5, except that 0b011 fills with zeroes instead of an increasing sequence.
+fn lz19_decomp_strategy() -> DecompressionStrategy {
+    DecompressionStrategy::new()
+        .insert_command(0b000, false, commands::direct_copy)
+        .insert_command(0b001, false, commands::byte_fill)
+        .insert_command(0b010, false, commands::word_fill)
+        .insert_command(0b011, false, commands::zero_fill)
+        .insert_command(0b100, false, commands::repeat_le)
+        .insert_command(0b101, false, commands::xor_repeat_le)
+        .insert_command(0b110, false, commands::negative_repeat)
+        .insert_command(0b111, true, commands::negative_xor_repeat_le)
+}
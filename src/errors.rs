@@ -1,5 +1,11 @@
-use thiserror::Error;
+//! Errors returned by the library. With the `std` feature they implement `std::error::Error`.
 
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::fmt;
+
+/// Copies of the compressed data and the partial output, kept when
+/// [`DecompressionOptions::set_error_buffers`](crate::DecompressionOptions::set_error_buffers) is
+/// set.
 #[derive(Debug, PartialEq)]
 pub struct DecompressionErrorInfo {
     source: Vec<u8>,
@@ -13,32 +19,236 @@ impl DecompressionErrorInfo {
             result: result.to_owned(),
         }
     }
+
+    /// The compressed data being decompressed.
+    pub fn source(&self) -> &[u8] {
+        &self.source
+    }
+
+    /// The output written before the error.
+    pub fn result(&self) -> &[u8] {
+        &self.result
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DecompressionErrorKind {
     UnsupportedFormat,
     InvalidCommand,
     IndexOutOfBounds,
+    InvalidOffset,
+    MismatchedPlaneSizes,
+    OutputOverflow,
+    UnsupportedFraming,
 }
 
-#[derive(Error, Debug, PartialEq)]
-#[error("Decompression Error")]
+impl fmt::Display for DecompressionErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            DecompressionErrorKind::UnsupportedFormat => "unsupported format",
+            DecompressionErrorKind::InvalidCommand => "invalid command",
+            DecompressionErrorKind::IndexOutOfBounds => "unexpected end of compressed data",
+            DecompressionErrorKind::InvalidOffset => "offset outside of the output",
+            DecompressionErrorKind::MismatchedPlaneSizes => "mismatched plane sizes",
+            DecompressionErrorKind::OutputOverflow => "output does not fit",
+            DecompressionErrorKind::UnsupportedFraming => "framing not supported by the format",
+        };
+        f.write_str(message)
+    }
+}
+
+/// Describes where decompression failed.
+///
+/// `offset` is the position in the compressed data of the command that failed, and
+/// `command_byte` is the first byte of that command. Errors that are not tied to a command, like
+/// an unsupported format, have no command byte.
+#[derive(Debug, PartialEq)]
 pub struct DecompressionError {
-    error_info: DecompressionErrorInfo,
     kind: DecompressionErrorKind,
+    offset: usize,
+    command_byte: Option<u8>,
+    output_len: usize,
+    error_info: Option<DecompressionErrorInfo>,
 }
 
 impl DecompressionError {
-    pub fn new(kind: DecompressionErrorKind, error_info: DecompressionErrorInfo) -> Self {
-        DecompressionError { error_info, kind }
+    pub fn new(
+        kind: DecompressionErrorKind,
+        offset: usize,
+        command_byte: Option<u8>,
+        output_len: usize,
+    ) -> Self {
+        DecompressionError {
+            kind,
+            offset,
+            command_byte,
+            output_len,
+            error_info: None,
+        }
+    }
+
+    pub fn set_error_info(mut self, error_info: DecompressionErrorInfo) -> Self {
+        self.error_info = Some(error_info);
+        self
+    }
+
+    pub fn kind(&self) -> DecompressionErrorKind {
+        self.kind
+    }
+
+    /// The offset in the compressed data of the command that failed.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The first byte of the command that failed.
+    pub fn command_byte(&self) -> Option<u8> {
+        self.command_byte
+    }
+
+    /// The number of bytes written before the error.
+    pub fn output_len(&self) -> usize {
+        self.output_len
+    }
+
+    /// Copies of the compressed data and the partial output, if they were requested.
+    pub fn error_info(&self) -> Option<&DecompressionErrorInfo> {
+        self.error_info.as_ref()
     }
 }
 
-#[derive(Error, Debug, PartialEq)]
+impl fmt::Display for DecompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Decompression failed: {} at offset {:#X}",
+            self.kind, self.offset
+        )?;
+        if let Some(command_byte) = self.command_byte {
+            write!(f, " (command byte {command_byte:#04X})")?;
+        }
+        write!(f, " after {:#X} bytes of output.", self.output_len)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecompressionError {}
+
+#[derive(Debug, PartialEq)]
 pub enum CompressionError {
-    #[error("Compression type \"{compression_type}\" is not supported.")]
     UnsupportedFormat { compression_type: String },
-    #[error("Compression failed. All data SHOULD be compressible for any format. This is a problem with the library.")]
     CompressionFailed,
+    VerificationFailed { offset: usize },
+    UnsupportedFraming { compression_type: String },
+    SourceTooLarge { size: usize },
+}
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompressionError::UnsupportedFormat { compression_type } => {
+                write!(
+                    f,
+                    "Compression type \"{compression_type}\" is not supported."
+                )
+            }
+            CompressionError::CompressionFailed => f.write_str(
+                "Compression failed. All data SHOULD be compressible for any format. This is a \
+                 problem with the library.",
+            ),
+            CompressionError::VerificationFailed { offset } => write!(
+                f,
+                "Verification failed. The compressed data does not decompress to the source, \
+                 starting at offset {offset:#X}. This is a problem with the library."
+            ),
+            CompressionError::UnsupportedFraming { compression_type } => write!(
+                f,
+                "Compression type \"{compression_type}\" only supports its terminator for framing."
+            ),
+            CompressionError::SourceTooLarge { size } => write!(
+                f,
+                "The source is {size:#X} bytes, which is too large for a 16 bit size prefix."
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CompressionError {}
+
+/// Describes why a format spec could not be loaded. Lines are numbered from 1.
+#[derive(Debug, PartialEq)]
+pub enum FormatSpecError {
+    InvalidLine { line: usize },
+    UnknownKey { line: usize, key: String },
+    InvalidValue { line: usize, key: String },
+    UnknownPrimitive { line: usize, name: String },
+    MissingKey { key: String },
+    MissingDirectCopy,
+}
+
+impl fmt::Display for FormatSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatSpecError::InvalidLine { line } => {
+                write!(f, "Line {line}: expected `key = value` or `[commands]`.")
+            }
+            FormatSpecError::UnknownKey { line, key } => {
+                write!(f, "Line {line}: unknown key \"{key}\".")
+            }
+            FormatSpecError::InvalidValue { line, key } => {
+                write!(f, "Line {line}: invalid value for \"{key}\".")
+            }
+            FormatSpecError::UnknownPrimitive { line, name } => {
+                write!(f, "Line {line}: unknown command \"{name}\".")
+            }
+            FormatSpecError::MissingKey { key } => write!(f, "The spec does not set \"{key}\"."),
+            FormatSpecError::MissingDirectCopy => f.write_str(
+                "The spec needs a direct_copy command for data that cannot be compressed.",
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FormatSpecError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn errors_implement_display() {
+        let error = CompressionError::VerificationFailed { offset: 0x12 };
+        assert_eq!(
+            error.to_string(),
+            "Verification failed. The compressed data does not decompress to the source, starting \
+             at offset 0x12. This is a problem with the library."
+        );
+        let error = CompressionError::UnsupportedFormat {
+            compression_type: "LZ9".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Compression type \"LZ9\" is not supported."
+        );
+        let error = CompressionError::SourceTooLarge { size: 0x10000 };
+        assert_eq!(
+            error.to_string(),
+            "The source is 0x10000 bytes, which is too large for a 16 bit size prefix."
+        );
+        let error = FormatSpecError::UnknownKey {
+            line: 3,
+            key: "color".to_string(),
+        };
+        assert_eq!(error.to_string(), "Line 3: unknown key \"color\".");
+        let error =
+            DecompressionError::new(DecompressionErrorKind::InvalidCommand, 2, Some(0xE0), 4);
+        assert_eq!(
+            error.to_string(),
+            "Decompression failed: invalid command at offset 0x2 (command byte 0xE0) after 0x4 \
+             bytes of output."
+        );
+    }
 }
@@ -0,0 +1,489 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::cmp;
+
+use crate::errors::FormatSpecError;
+use crate::{compression_commands as comp, decompression_commands as decomp};
+use crate::{CommandConfiguration, CompressionStrategy, DecompressionStrategy, LzCodec};
+
+/// The commands a spec can use, by name.
+const PRIMITIVES: [(&str, comp::CommandCallback, decomp::CommandCallback); 14] = [
+    ("direct_copy", comp::direct_copy, decomp::direct_copy),
+    ("byte_fill", comp::byte_fill, decomp::byte_fill),
+    ("word_fill", comp::word_fill, decomp::word_fill),
+    (
+        "increasing_fill",
+        comp::increasing_fill,
+        decomp::increasing_fill,
+    ),
+    ("zero_fill", comp::zero_fill, decomp::zero_fill),
+    ("repeat_le", comp::repeat_le, decomp::repeat_le),
+    ("repeat_be", comp::repeat_be, decomp::repeat_be),
+    ("xor_repeat_le", comp::xor_repeat_le, decomp::xor_repeat_le),
+    ("xor_repeat_be", comp::xor_repeat_be, decomp::xor_repeat_be),
+    (
+        "negative_repeat",
+        comp::negative_repeat,
+        decomp::negative_repeat,
+    ),
+    (
+        "negative_xor_repeat",
+        comp::negative_xor_repeat,
+        decomp::negative_xor_repeat_le,
+    ),
+    ("signed_repeat", comp::signed_repeat, decomp::signed_repeat),
+    (
+        "signed_bit_reversed_repeat",
+        comp::signed_bit_reversed_repeat,
+        decomp::signed_bit_reversed_repeat,
+    ),
+    (
+        "signed_backwards_repeat",
+        comp::signed_backwards_repeat,
+        decomp::signed_backwards_repeat,
+    ),
+];
+
+/// The longest command the built-in formats allow.
+const DEFAULT_MAX_COMMAND_SIZE: usize = 1024;
+
+#[derive(PartialEq)]
+enum Section {
+    Format,
+    Commands,
+}
+
+struct CommandSpec {
+    cmd_num: u8,
+    primitive: usize,
+}
+
+/// Builds an LZ format from a text description, so a format can be tried out without writing
+/// Rust.
+///
+/// The spec is a list of `key = value` lines, with `#` starting a comment:
+///
+/// ```text
+/// name = "MyGame"                 # required
+/// description = "MyGame graphics"
+/// command_bits = 3                # width of the command number in a header, 1 to 4
+/// max_command_size = 1024         # at most 2^(16 - 2 * command_bits)
+/// terminator = 0xFF
+///
+/// [commands]
+/// 0 = "direct_copy"
+/// 1 = "byte_fill"
+/// 2 = "word_fill"
+/// 3 = "increasing_fill"
+/// 4 = "repeat_le"
+/// 5 = "xor_repeat_le"
+/// 6 = "negative_repeat"
+/// 7 = "negative_xor_repeat"
+/// ```
+///
+/// The `[commands]` section maps command numbers to the commands in
+/// [`compression_commands`](crate::compression_commands), except that `negative_xor_repeat`
+/// reads a little endian offset. The command numbered with all ones only has a long form header,
+/// since its short form marks the long form. Exactly one command must be `direct_copy`, which is
+/// used for data that nothing else can compress.
+///
+/// Only `name` is required. The rest default to the layout of the built-in formats.
+pub fn parse_format_spec(spec: &str) -> Result<LzCodec, FormatSpecError> {
+    let mut name = None;
+    let mut description = String::new();
+    let mut num_cmd_bits = 3;
+    let mut max_command_size = None;
+    let mut terminator = 0xFF;
+    let mut terminator_line = 0;
+    let mut commands: Vec<CommandSpec> = Vec::new();
+    let mut section = Section::Format;
+
+    for (line_idx, line) in spec.lines().enumerate() {
+        let line_num = line_idx + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "[commands]" {
+            section = Section::Commands;
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(FormatSpecError::InvalidLine { line: line_num })?;
+        let key = key.trim();
+        let value = value.trim();
+        let invalid_value = || FormatSpecError::InvalidValue {
+            line: line_num,
+            key: key.to_string(),
+        };
+
+        if section == Section::Commands {
+            let cmd_num = parse_number(key)
+                .and_then(|cmd_num| u8::try_from(cmd_num).ok())
+                .filter(|&cmd_num| commands.iter().all(|command| command.cmd_num != cmd_num))
+                .ok_or_else(invalid_value)?;
+            let primitive_name = parse_string(value).unwrap_or(value);
+            let primitive = PRIMITIVES
+                .iter()
+                .position(|&(name, _, _)| name == primitive_name)
+                .ok_or_else(|| FormatSpecError::UnknownPrimitive {
+                    line: line_num,
+                    name: primitive_name.to_string(),
+                })?;
+            commands.push(CommandSpec { cmd_num, primitive });
+            continue;
+        }
+
+        match key {
+            "name" => name = Some(parse_string(value).ok_or_else(invalid_value)?),
+            "description" => {
+                description = parse_string(value).ok_or_else(invalid_value)?.to_string();
+            }
+            "command_bits" => {
+                num_cmd_bits = parse_number(value)
+                    .filter(|bits| (1..=DecompressionStrategy::MAX_NUM_CMD_BITS).contains(bits))
+                    .ok_or_else(invalid_value)?;
+            }
+            "max_command_size" => {
+                let size = parse_number(value).ok_or_else(invalid_value)?;
+                max_command_size = Some((line_num, size));
+            }
+            "terminator" => {
+                terminator = parse_number(value)
+                    .and_then(|byte| u8::try_from(byte).ok())
+                    .ok_or_else(invalid_value)?;
+                terminator_line = line_num;
+            }
+            _ => {
+                return Err(FormatSpecError::UnknownKey {
+                    line: line_num,
+                    key: key.to_string(),
+                })
+            }
+        }
+    }
+
+    let name = name.ok_or_else(|| FormatSpecError::MissingKey {
+        key: "name".to_string(),
+    })?;
+    let size_limit = 1 << (16 - 2 * num_cmd_bits);
+    let max_block_size = match max_command_size {
+        Some((line_num, size)) if size == 0 || size > size_limit => {
+            return Err(FormatSpecError::InvalidValue {
+                line: line_num,
+                key: "max_command_size".to_string(),
+            })
+        }
+        Some((_, size)) => size,
+        None => cmp::min(DEFAULT_MAX_COMMAND_SIZE, size_limit),
+    };
+    // Command numbers are checked here, once `command_bits` is known.
+    let marker = (1 << num_cmd_bits) - 1;
+    if let Some(command) = commands.iter().find(|command| command.cmd_num > marker) {
+        let line = find_command_line(spec, command.cmd_num);
+        return Err(FormatSpecError::InvalidValue {
+            line,
+            key: command.cmd_num.to_string(),
+        });
+    }
+    commands.sort_by_key(|command| command.cmd_num);
+    let is_direct_copy = |command: &CommandSpec| PRIMITIVES[command.primitive].0 == "direct_copy";
+    let fallback = commands
+        .iter()
+        .position(is_direct_copy)
+        .ok_or(FormatSpecError::MissingDirectCopy)?;
+    if let Some(command) = commands[fallback + 1..]
+        .iter()
+        .find(|&command| is_direct_copy(command))
+    {
+        let line = find_command_line(spec, command.cmd_num);
+        return Err(FormatSpecError::InvalidValue {
+            line,
+            key: command.cmd_num.to_string(),
+        });
+    }
+    let fallback_config = CommandConfiguration::new(
+        commands[fallback].cmd_num,
+        num_cmd_bits,
+        max_block_size,
+        commands[fallback].cmd_num == marker,
+    );
+    if fallback_config.avoid_terminator(terminator).max_block_size == 0 {
+        return Err(FormatSpecError::InvalidValue {
+            line: terminator_line,
+            key: "terminator".to_string(),
+        });
+    }
+
+    let mut compression_strategy =
+        CompressionStrategy::new(num_cmd_bits, max_block_size).set_terminator(terminator);
+    let mut decompression_strategy = DecompressionStrategy::new()
+        .set_num_cmd_bits(num_cmd_bits)
+        .set_terminator(terminator);
+    for (i, command) in commands.iter().enumerate() {
+        let (_, compression_callback, decompression_callback) = PRIMITIVES[command.primitive];
+        let is_extended_only = command.cmd_num == marker;
+        compression_strategy = if i == fallback {
+            compression_strategy.set_fallback_command(
+                command.cmd_num,
+                is_extended_only,
+                compression_callback,
+            )
+        } else {
+            compression_strategy.insert_command(
+                command.cmd_num,
+                is_extended_only,
+                compression_callback,
+            )
+        };
+        decompression_strategy = decompression_strategy.insert_command(
+            command.cmd_num,
+            is_extended_only,
+            decompression_callback,
+        );
+    }
+    Ok(
+        LzCodec::new(name, compression_strategy, decompression_strategy)
+            .set_description(&description),
+    )
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut is_quoted = false;
+    for (i, char) in line.char_indices() {
+        match char {
+            '"' => is_quoted = !is_quoted,
+            '#' if !is_quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Reads a decimal or `0x` prefixed hexadecimal number.
+fn parse_number(value: &str) -> Option<usize> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_string(value: &str) -> Option<&str> {
+    value.strip_prefix('"')?.strip_suffix('"')
+}
+
+/// Finds the line of a command, for errors that are only found after the whole spec is read.
+fn find_command_line(spec: &str, cmd_num: u8) -> usize {
+    spec.lines()
+        .position(|line| {
+            let key = strip_comment(line)
+                .split_once('=')
+                .map(|(key, _)| key.trim());
+            key.and_then(parse_number) == Some(cmd_num as usize)
+        })
+        .map_or(0, |line_idx| line_idx + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, Codec, CompressionOptions, CompressionType, DecompressionOptions};
+
+    const LZ5_SPEC: &str = r#"
+        # LC_LZ5, written out as a spec.
+        name = "LZ5 copy"
+        description = "Lunar Compress LC_LZ5" # same as the built-in format
+
+        [commands]
+        0 = "direct_copy"
+        1 = "byte_fill"
+        2 = "word_fill"
+        3 = "increasing_fill"
+        4 = "repeat_le"
+        5 = "xor_repeat_le"
+        6 = "negative_repeat"
+        7 = "negative_xor_repeat"
+    "#;
+
+    fn get_test_data() -> Vec<u8> {
+        let mut data = b"ABABABAB CCCCCCCC ABABABAB 0123456789".to_vec();
+        data.extend((0..0x200_u32).map(|i| (i * i / 5) as u8));
+        data
+    }
+
+    #[test]
+    fn spec_matches_built_in_format() {
+        let codec = parse_format_spec(LZ5_SPEC).unwrap();
+        assert_eq!(codec.name(), "LZ5 copy");
+        assert_eq!(codec.metadata().description, "Lunar Compress LC_LZ5");
+
+        let source = get_test_data();
+        let compressed = codec
+            .compress(&source, &CompressionOptions::default())
+            .unwrap();
+        assert_eq!(compressed, compress(&source, CompressionType::LZ5).unwrap());
+        let decompressed = codec
+            .decompress(&compressed, &DecompressionOptions::default())
+            .unwrap();
+        assert_eq!(decompressed, source);
+    }
+
+    #[test]
+    fn spec_supports_other_layouts() {
+        let spec = r#"
+            name = "Narrow"
+            command_bits = 2
+            max_command_size = 0x800
+            terminator = 0x00
+
+            [commands]
+            0 = "repeat_be"
+            1 = "direct_copy"
+            2 = "byte_fill"
+            3 = "word_fill"
+        "#;
+        let codec = parse_format_spec(spec).unwrap();
+        let mut source = get_test_data();
+        source.extend([0xAA; 0x700]);
+        let options = CompressionOptions::new().set_verify(true);
+        let compressed = codec.compress(&source, &options).unwrap();
+        assert_eq!(compressed.last(), Some(&0x00));
+        // The 0x700 byte fill fits in one long form command with a 12 bit size.
+        assert!(compressed
+            .windows(3)
+            .any(|bytes| bytes == [0xE6, 0xFF, 0xAA]));
+        let decompressed = codec
+            .decompress(&compressed, &DecompressionOptions::default())
+            .unwrap();
+        assert_eq!(decompressed, source);
+    }
+
+    #[test]
+    fn spec_decodes_headers_with_four_command_bits() {
+        let spec = r#"
+            name = "Wide"
+            command_bits = 4
+            [commands]
+            0 = "direct_copy"
+            9 = "byte_fill"
+            15 = "increasing_fill"
+        "#;
+        let codec = parse_format_spec(spec).unwrap();
+        let compressed = [0x01, 0xA1, 0xA2, 0x92, 0xB1, 0xF9, 0x02, 0xC1, 0xFF];
+        let decompressed = codec
+            .decompress(&compressed, &DecompressionOptions::default())
+            .unwrap();
+        assert_eq!(
+            decompressed,
+            [0xA1, 0xA2, 0xB1, 0xB1, 0xB1, 0xC1, 0xC1, 0xC1]
+        );
+
+        let source = get_test_data();
+        let options = CompressionOptions::new().set_verify(true);
+        let compressed = codec.compress(&source, &options).unwrap();
+        let decompressed = codec
+            .decompress(&compressed, &DecompressionOptions::default())
+            .unwrap();
+        assert_eq!(decompressed, source);
+    }
+
+    #[test]
+    fn spec_output_never_contains_early_terminator() {
+        // A 4 byte fill would start with 0x23, so fills are split.
+        let spec = LZ5_SPEC.replace("[commands]", "terminator = 0x23\n[commands]");
+        let codec = parse_format_spec(&spec).unwrap();
+        let mut source = [0x0A; 0x20].to_vec();
+        source.extend((0..0x40).map(|i| (i % 4) as u8 * 0x11 + 4));
+        let options = CompressionOptions::new().set_verify(true);
+        let compressed = codec.compress(&source, &options).unwrap();
+        assert_eq!(
+            compressed.iter().position(|&byte| byte == 0x23),
+            Some(compressed.len() - 1)
+        );
+        let decompressed = codec
+            .decompress(&compressed, &DecompressionOptions::default())
+            .unwrap();
+        assert_eq!(decompressed, source);
+
+        // Every direct copy header would be the terminator, so nothing could be written.
+        let spec = LZ5_SPEC.replace("[commands]", "terminator = 0x00\n[commands]");
+        assert_eq!(
+            parse_format_spec(&spec).err(),
+            Some(FormatSpecError::InvalidValue {
+                line: 6,
+                key: "terminator".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn spec_reports_errors_with_line() {
+        let cases = [
+            (
+                "name = \"A\"\ncolor = 3",
+                FormatSpecError::UnknownKey {
+                    line: 2,
+                    key: "color".to_string(),
+                },
+            ),
+            (
+                "name = \"A\"\n[commands]\n0 = \"direct_copy\"\n1 = \"teleport\"",
+                FormatSpecError::UnknownPrimitive {
+                    line: 4,
+                    name: "teleport".to_string(),
+                },
+            ),
+            (
+                "name = \"A\"\ncommand_bits = 5",
+                FormatSpecError::InvalidValue {
+                    line: 2,
+                    key: "command_bits".to_string(),
+                },
+            ),
+            (
+                "name = \"A\"\n[commands]\n0 = \"direct_copy\"\n8 = \"byte_fill\"",
+                FormatSpecError::InvalidValue {
+                    line: 4,
+                    key: "8".to_string(),
+                },
+            ),
+            (
+                "name = \"A\"\n[commands]\n0 = \"direct_copy\"\n0 = \"byte_fill\"",
+                FormatSpecError::InvalidValue {
+                    line: 4,
+                    key: "0".to_string(),
+                },
+            ),
+            (
+                "name = \"A\"\n[commands]\n0 = \"direct_copy\"\n1 = \"direct_copy\"",
+                FormatSpecError::InvalidValue {
+                    line: 4,
+                    key: "1".to_string(),
+                },
+            ),
+            (
+                "name = \"A\"\nnot a line",
+                FormatSpecError::InvalidLine { line: 2 },
+            ),
+            (
+                "[commands]\n0 = \"direct_copy\"",
+                FormatSpecError::MissingKey {
+                    key: "name".to_string(),
+                },
+            ),
+            (
+                "name = \"A\"\n[commands]\n0 = \"byte_fill\"",
+                FormatSpecError::MissingDirectCopy,
+            ),
+        ];
+        for (spec, expected) in cases {
+            assert_eq!(parse_format_spec(spec).err(), Some(expected), "{spec}");
+        }
+    }
+}
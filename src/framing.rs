@@ -0,0 +1,30 @@
+/// How the end of a compressed stream is found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// The stream ends with the format's terminator byte. This is `0xFF` for the built-in formats,
+    /// and strategies and format specs can choose another.
+    #[default]
+    Terminator,
+    /// The stream has no terminator, and decompression stops once this many bytes have been
+    /// written. The size is not stored, so it is only used when decompressing.
+    OutputSize(usize),
+    /// The stream has no terminator, and starts with the decompressed size as a 16 bit little
+    /// endian number.
+    SizePrefix,
+}
+
+/// The length of the header written by [`Framing::SizePrefix`].
+pub(crate) const SIZE_PREFIX_LEN: usize = 2;
+
+/// Reads the decompressed size at the start of a [`Framing::SizePrefix`] stream.
+pub(crate) fn read_size_prefix(source: &[u8]) -> Option<usize> {
+    match source {
+        [low, high, ..] => Some(u16::from_le_bytes([*low, *high]).into()),
+        _ => None,
+    }
+}
+
+/// Builds the header of a [`Framing::SizePrefix`] stream, if `size` fits in it.
+pub(crate) fn build_size_prefix(size: usize) -> Option<[u8; SIZE_PREFIX_LEN]> {
+    u16::try_from(size).ok().map(u16::to_le_bytes)
+}
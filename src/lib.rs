@@ -4,13 +4,20 @@
 
 ## Supported Formats
 
-- [ ] LZ1
-- [ ] LZ19
-- [ ] LZ2
-- [ ] LZ3
+- [x] LZ1
+- [x] LZ19
+- [x] LZ2
+- [x] LZ3
+- [x] LZ4
 - [x] LZ5
-- [ ] RLE1
-- [ ] RLE2
+- [x] RLE1
+- [x] RLE2
+
+## Features
+
+- `std` (default): the streaming `Compressor` and `Decompressor`, the `cli` module, and
+  `std::error::Error` for the error types. Without it the library is `no_std` and only needs
+  `alloc`.
 
 ## Example
 
@@ -27,19 +34,49 @@ fn compress_data(decompressed_data: &[u8]) -> Result<Vec<u8>, CompressionError>
 ```
 
 */
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+extern crate alloc;
+
 use core::fmt;
 
+#[cfg(feature = "std")]
+pub mod cli;
+mod codec;
 mod compression;
 mod decompression;
+mod format_spec;
+mod framing;
 
 pub mod errors;
-pub use compression::compress;
-pub use decompression::decompress;
+pub use codec::{Codec, CodecMetadata, CodecRegistry, LzCodec};
+#[cfg(feature = "std")]
+pub use compression::Compressor;
+pub use compression::{
+    command_callbacks as compression_commands, compress, compress_verified, compress_with_level,
+    compress_with_options, compress_with_strategy, verify, verify_with_framing,
+    CommandConfiguration, CompressionLevel, CompressionOptions, CompressionStrategy,
+};
+#[cfg(feature = "std")]
+pub use decompression::Decompressor;
+pub use decompression::{
+    command_callbacks as decompression_commands, decompress, decompress_into, decompress_with_info,
+    decompress_with_options, decompress_with_strategy, disassemble, DecompressionInfo,
+    DecompressionOptions, DecompressionStrategy, DisassembledCommand, OutputBuffer,
+};
+pub use format_spec::parse_format_spec;
+pub use framing::Framing;
 
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompressionType {
+    LZ1,
+    LZ2,
+    LZ3,
+    LZ4,
     LZ5,
+    LZ19,
+    RLE1,
+    RLE2,
 }
 
 impl fmt::Display for CompressionType {
@@ -60,4 +97,218 @@ mod tests {
         assert_eq!(decompressed, source);
         assert!(compressed.len() < decompressed.len());
     }
+
+    #[test]
+    fn compress_and_decompress_simple_lz1() {
+        let source = vec![
+            0xA, 0xA, 0xA, 0xA, 1, 2, 3, 4, 3, 2, 1, 0xB, 1, 2, 3, 4, 3, 2, 1,
+        ];
+        let compressed = compress(&source, CompressionType::LZ1).unwrap();
+        let decompressed = decompress(&compressed, CompressionType::LZ1).unwrap();
+        assert_eq!(decompressed, source);
+        assert!(compressed.len() < decompressed.len());
+    }
+
+    #[test]
+    fn compress_and_decompress_simple_lz2() {
+        let source = vec![
+            0xA, 0xA, 0xA, 0xA, 1, 2, 3, 4, 3, 2, 1, 0xB, 1, 2, 3, 4, 3, 2, 1,
+        ];
+        let compressed = compress(&source, CompressionType::LZ2).unwrap();
+        let decompressed = decompress(&compressed, CompressionType::LZ2).unwrap();
+        assert_eq!(decompressed, source);
+        assert!(compressed.len() < decompressed.len());
+    }
+
+    #[test]
+    fn compress_and_decompress_past_64_kib() {
+        // Short runs of random bytes, whose tail repeats bytes past 0x10000, which a two byte
+        // absolute offset cannot reach.
+        let mut state: u32 = 0x12345678;
+        let mut source = Vec::new();
+        while source.len() < 0x11000 {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            let run_size = (state >> 8) as usize % 12 + 1;
+            source.extend(vec![(state >> 24) as u8; run_size]);
+        }
+        source.extend_from_within(0x10800..0x10840);
+        for compression_type in [
+            CompressionType::LZ2,
+            CompressionType::LZ4,
+            CompressionType::LZ5,
+        ] {
+            let compressed = compress(&source, compression_type).unwrap();
+            let decompressed = decompress(&compressed, compression_type).unwrap();
+            assert!(decompressed == source, "{compression_type:?}");
+        }
+    }
+
+    #[test]
+    fn compress_and_decompress_simple_lz3() {
+        let source = vec![
+            0, 0, 0, 0, 1, 2, 3, 4, 4, 3, 2, 1, 0x80, 0x40, 0xC0, 0x20, 1, 2, 3, 4,
+        ];
+        let compressed = compress(&source, CompressionType::LZ3).unwrap();
+        let decompressed = decompress(&compressed, CompressionType::LZ3).unwrap();
+        assert_eq!(decompressed, source);
+        assert!(compressed.len() < decompressed.len());
+    }
+
+    #[test]
+    fn compress_and_decompress_simple_lz4() {
+        let source = vec![
+            0xA, 0xA, 0xA, 0xA, 1, 2, 3, 4, 0xFE, 0xFD, 0xFC, 0xFB, 1, 2, 3, 4, 3, 2, 1,
+        ];
+        let compressed = compress(&source, CompressionType::LZ4).unwrap();
+        let decompressed = decompress(&compressed, CompressionType::LZ4).unwrap();
+        assert_eq!(decompressed, source);
+        assert!(compressed.len() < decompressed.len());
+    }
+
+    #[test]
+    fn compress_and_decompress_simple_lz19() {
+        let source = vec![
+            0, 0, 0, 0, 1, 2, 3, 4, 3, 2, 1, 0xB, 1, 2, 3, 4, 0xFE, 0xFD, 0xFC,
+        ];
+        let compressed = compress(&source, CompressionType::LZ19).unwrap();
+        let decompressed = decompress(&compressed, CompressionType::LZ19).unwrap();
+        assert_eq!(decompressed, source);
+        assert!(compressed.len() < decompressed.len());
+    }
+
+    #[test]
+    fn compress_and_decompress_simple_rle1() {
+        let source = vec![0xA, 0xA, 0xA, 0xA, 1, 2, 3, 4, 3, 2, 1, 0xB, 0xB, 0xB, 0xB];
+        let compressed = compress(&source, CompressionType::RLE1).unwrap();
+        let decompressed = decompress(&compressed, CompressionType::RLE1).unwrap();
+        assert_eq!(decompressed, source);
+        assert!(compressed.len() < decompressed.len());
+    }
+
+    #[test]
+    fn compress_and_decompress_simple_rle2() {
+        let source = vec![0xA, 1, 0xA, 2, 0xA, 3, 0xA, 4, 0xA, 5, 0xA, 6, 0xA, 7, 0xA];
+        let compressed = compress(&source, CompressionType::RLE2).unwrap();
+        let decompressed = decompress(&compressed, CompressionType::RLE2).unwrap();
+        assert_eq!(decompressed, source);
+        assert!(compressed.len() < decompressed.len());
+    }
+
+    #[test]
+    fn compress_and_decompress_with_every_level() {
+        let source = b"TGTGTGTCCTTAGAGGGGTCTTTT_APPLE_APPLE_0123_3210".to_vec();
+        let levels = [
+            CompressionLevel::Fast,
+            CompressionLevel::Lazy,
+            CompressionLevel::Optimal,
+        ];
+        for level in levels {
+            let compressed = compress_with_level(&source, CompressionType::LZ5, level).unwrap();
+            let decompressed = decompress(&compressed, CompressionType::LZ5).unwrap();
+            assert_eq!(decompressed, source);
+        }
+    }
+
+    #[test]
+    fn compress_without_xor_or_increasing_fill() {
+        let mut source = b"ABCDEFGH_APPLE_".to_vec();
+        source.extend(b"APPLE".iter().map(|&x| x ^ 0xFF));
+        let options = CompressionOptions::new()
+            .set_xor_commands(false)
+            .set_increasing_fill(false)
+            .set_search_depth(1);
+        let compressed = compress_with_options(&source, CompressionType::LZ5, &options).unwrap();
+        let decompressed = decompress(&compressed, CompressionType::LZ5).unwrap();
+        assert_eq!(decompressed, source);
+        let commands = disassemble(&compressed, CompressionType::LZ5).unwrap();
+        assert!(commands.iter().all(|command| command.cmd_num != 0b011
+            && command.cmd_num != 0b101
+            && command.cmd_num != 0b111));
+    }
+
+    /// A 64 KiB block of tiles, with blank runs and repeated tiles, should compress in
+    /// milliseconds. Unoptimized builds get a looser limit.
+    #[test]
+    fn compressing_64_kib_is_fast() {
+        let mut seed: u32 = 1;
+        let mut next_random = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as usize
+        };
+        let mut source: Vec<u8> = Vec::new();
+        while source.len() < 0x10000 {
+            match next_random() % 4 {
+                0 => source.extend_from_slice(&[0; 16 * 8]),
+                1 if !source.is_empty() => {
+                    let start = next_random() % (source.len() / 16) * 16;
+                    source.extend_from_within(start..start + 16);
+                }
+                _ => source.extend((0..16).map(|_| next_random() as u8 & 0x3C)),
+            }
+        }
+        source.truncate(0x10000);
+
+        let limit = if cfg!(debug_assertions) { 3000 } else { 300 };
+        for compression_type in [CompressionType::LZ2, CompressionType::LZ5] {
+            let start = std::time::Instant::now();
+            compress(&source, compression_type).unwrap();
+            let elapsed = start.elapsed();
+            assert!(
+                elapsed.as_millis() < limit,
+                "{compression_type} took {elapsed:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn compress_and_decompress_incompressible_data() {
+        let compression_types = [
+            CompressionType::LZ1,
+            CompressionType::LZ2,
+            CompressionType::LZ3,
+            CompressionType::LZ4,
+            CompressionType::LZ5,
+            CompressionType::LZ19,
+            CompressionType::RLE1,
+            CompressionType::RLE2,
+        ];
+        let levels = [
+            CompressionLevel::Fast,
+            CompressionLevel::Lazy,
+            CompressionLevel::Optimal,
+        ];
+        let sizes = [
+            0, 1, 2, 31, 32, 33, 127, 128, 1023, 1024, 1025, 2047, 2048, 2049, 3000,
+        ];
+        let mut state: u32 = 0x12345678;
+        for size in sizes {
+            let source: Vec<u8> = (0..size)
+                .map(|_| {
+                    state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                    (state >> 24) as u8
+                })
+                .collect();
+            for compression_type in compression_types {
+                for level in levels {
+                    let compressed = compress_with_level(&source, compression_type, level).unwrap();
+                    let decompressed = decompress(&compressed, compression_type).unwrap();
+                    assert_eq!(decompressed, source, "{compression_type} {level:?} {size}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compress_verified_round_trips() {
+        let source = b"TGTGTGTCCTTAGAGGGGTCTTTT_APPLE_APPLE_0123_3210".to_vec();
+        let compression_types = [
+            CompressionType::LZ3,
+            CompressionType::LZ5,
+            CompressionType::RLE2,
+        ];
+        for compression_type in compression_types {
+            let compressed = compress_verified(&source, compression_type).unwrap();
+            assert_eq!(compressed, compress(&source, compression_type).unwrap());
+        }
+    }
 }